@@ -6,34 +6,186 @@ pub mod github {
     pub const DEFAULT_REPO: &str = "my-notion";
     pub const DEFAULT_BRANCH: &str = "main";
     pub const BASE64_ENCODING: &str = "base64";
+    pub const NO_ENCODING: &str = "none";
+    pub const RATE_LIMIT_REMAINING_HEADER: &str = "x-ratelimit-remaining";
+    pub const RATE_LIMIT_RESET_HEADER: &str = "x-ratelimit-reset";
+    pub const RETRY_AFTER_HEADER: &str = "retry-after";
+    pub const COMMITS_PER_PAGE: u32 = 100;
+    pub const REFS_PER_PAGE: u32 = 100;
+    pub const TREE_CACHE_TTL_SECS: u64 = 300;
+    pub const TREE_TYPE_BLOB: &str = "blob";
+    pub const TREE_TYPE_TREE: &str = "tree";
+    pub const FILE_MODE: &str = "100644";
+    // Under the pull request write policy, edits go to `notes/<topic>`
+    pub const TOPIC_BRANCH_PREFIX: &str = "notes/";
+    pub const PULL_REQUEST_BODY: &str = "Opened by get-my-notion-mcp for review.";
+    pub const PULL_REQUEST_EXISTS: &str = "A pull request already exists";
+    // Fragments of GitHub's 422 messages that mean a write's base has moved
+    pub const STALE_WRITE_MESSAGES: &[&str] = &["not a fast forward", "\"sha\" wasn't supplied", "Reference already exists"];
+    // Transient failures (connection errors and 5xx) are retried with linear backoff
+    pub const MAX_RETRIES: u32 = 2;
+    pub const RETRY_BACKOFF_MS: u64 = 250;
+
+    // Environment variable holding the token used to authenticate requests
+    pub const TOKEN_ENV: &str = "GITHUB_TOKEN";
 }
 
 // MCP Protocol related constants
 pub mod mcp {
-    pub const PROTOCOL_VERSION: &str = "2024-11-05";
+    // Supported protocol revisions, oldest first
+    pub const PROTOCOL_VERSION_2024_11_05: &str = "2024-11-05";
+    pub const PROTOCOL_VERSION_2025_03_26: &str = "2025-03-26";
+    pub const PROTOCOL_VERSION_2025_06_18: &str = "2025-06-18";
     pub const SERVER_NAME: &str = "get-my-notion-mcp";
-    pub const SERVER_VERSION: &str = "0.2.4";
+    pub const SERVER_VERSION: &str = env!("CARGO_PKG_VERSION");
+    pub const SERVER_TITLE: &str = "My Notion";
+    pub const SERVER_WEBSITE_URL: &str = "https://github.com/ParkJong-Hun/get-my-notion-mcp";
     pub const SERVER_DESCRIPTION: &str = "MCP server for accessing my-notion GitHub repository";
     
     // Tool names
     pub const TOOL_LIST_FILES: &str = "list_files";
     pub const TOOL_GET_FILE_CONTENT: &str = "get_file_content";
     pub const TOOL_GET_LATEST_COMMIT: &str = "get_latest_commit";
+    pub const TOOL_SUMMARIZE_NOTE: &str = "summarize_note";
+    pub const TOOL_CREATE_OR_UPDATE_FILE: &str = "create_or_update_file";
+    pub const TOOL_DELETE_FILE: &str = "delete_file";
+    pub const TOOL_COMMIT_CHANGES: &str = "commit_changes";
+    pub const TOOL_CREATE_BRANCH: &str = "create_branch";
+    pub const TOOL_OPEN_PULL_REQUEST: &str = "open_pull_request";
     
     // Resource URIs
     pub const RESOURCE_REPO_INFO: &str = "notion://repo/info";
+    pub const RESOURCE_FILE_TEMPLATE: &str = "notion://file/{path}";
+    pub const RESOURCE_FILE_PREFIX: &str = "notion://file/";
+    
+    // How long a request sent to the client (e.g. roots/list) may go unanswered
+    pub const CLIENT_REQUEST_TIMEOUT_SECS: u64 = 120;
+
+    // Token budget asked of the client's model for a summary
+    pub const SUMMARY_MAX_TOKENS: u32 = 1024;
+
+    // How many similar paths are offered when a requested file does not exist
+    pub const MAX_PATH_SUGGESTIONS: usize = 5;
+
+    // How many recent week starts are offered for the weekly_review date
+    pub const WEEKLY_REVIEW_DATE_SUGGESTIONS: usize = 8;
+    
+    // Items per page of tools/list, resources/list and list_files
+    pub const DEFAULT_PAGE_SIZE: usize = 50;
+    
+    // Upper bound on requests handled at the same time per connection
+    pub const MAX_CONCURRENT_REQUESTS: usize = 16;
+    
+    // Request methods understood by the server
+    pub const REQUEST_METHODS: &[&str] = &[
+        "initialize",
+        "ping",
+        "tools/list",
+        "tools/call",
+        "resources/list",
+        "resources/read",
+        "resources/templates/list",
+        "prompts/list",
+        "prompts/get",
+        "completion/complete",
+        "logging/setLevel",
+    ];
+    
+    // Logger names used in `notifications/message`
+    pub const LOGGER_SERVER: &str = "server";
+    pub const LOGGER_GITHUB: &str = "github";
+    pub const LOGGER_TOOLS: &str = "tools";
+    
+    // Prompt names
+    pub const PROMPT_SUMMARIZE_NOTE: &str = "summarize_note";
+    pub const PROMPT_WEEKLY_REVIEW: &str = "weekly_review";
+    pub const PROMPT_FIND_RELATED_NOTES: &str = "find_related_notes";
     
     // Parameter names
     pub const PARAM_PATH: &str = "path";
     pub const PARAM_MESSAGE: &str = "message";
+    pub const PARAM_CONTENT: &str = "content";
+    pub const PARAM_DATE: &str = "date";
+    pub const PARAM_TOPIC: &str = "topic";
+    pub const PARAM_REF: &str = "ref";
+    pub const PARAM_CURSOR: &str = "cursor";
+    
+    // Tool call policies: argument redaction and default call limits
+    pub const REDACTED: &str = "[redacted]";
+    pub const REDACTED_ARGUMENTS: &[&str] = &[PARAM_CONTENT];
+    pub const DEFAULT_TOOL_TIMEOUT_SECS: u64 = 60;
+    pub const DEFAULT_MAX_RESULT_BYTES: usize = 1024 * 1024;
+    pub const DEFAULT_MAX_CONCURRENT_TOOL_CALLS: usize = 4;
+
+    // Completion limits
+    pub const MAX_COMPLETION_VALUES: usize = 100;
+}
+
+// HTTP transport related constants
+pub mod http {
+    pub const MCP_ENDPOINT: &str = "/mcp";
+    pub const SESSION_ID_HEADER: &str = "mcp-session-id";
+    pub const PROTOCOL_VERSION_HEADER: &str = "mcp-protocol-version";
+    // Legacy HTTP+SSE transport (protocol revision 2024-11-05)
+    pub const SSE_ENDPOINT: &str = "/sse";
+    pub const MESSAGES_ENDPOINT: &str = "/messages";
+    pub const SESSION_ID_QUERY: &str = "sessionId";
+    pub const DEFAULT_LISTEN: &str = "127.0.0.1:8080";
+    // Server-initiated messages held for a session's GET stream; more are dropped
+    pub const SESSION_BUFFER_SIZE: usize = 256;
+    // Sessions without requests for this long are ended
+    pub const SESSION_IDLE_TIMEOUT_SECS: u64 = 30 * 60;
+    pub const SESSION_SWEEP_INTERVAL_SECS: u64 = 60;
 }
 
 // Error messages
 pub mod errors {
     pub const GITHUB_API_FAILED: &str = "GitHub API request failed";
+    pub const NOT_FOUND: &str = "Not found";
+    pub const UNAUTHORIZED: &str = "GitHub denied access to the repository";
+    pub const RATE_LIMITED: &str = "GitHub API rate limit exceeded";
+    pub const CONFLICT: &str = "Changed since the given SHA; fetch it again and retry";
+    pub const REJECTED: &str = "GitHub rejected the request";
+    pub const CHANGES_REQUIRED: &str = "At least one change is required";
+    pub const DUPLICATE_CHANGE: &str = "Each path may only be changed once";
+    pub const FILE_TOO_LARGE: &str = "File is too large to fetch";
+    pub const INVALID_ARGUMENT: &str = "Invalid argument";
+    pub const TIMED_OUT: &str = "Timed out";
+    pub const RESULT_TRUNCATED: &str = "Result truncated";
+    pub const RESULT_TOO_LARGE: &str = "Structured result exceeds the size limit";
+    pub const CLIENT_REQUEST_FAILED: &str = "Client request failed";
+    pub const CLIENT_REQUEST_TIMED_OUT: &str = "Client did not answer the request in time";
+    pub const SAMPLING_UNSUPPORTED: &str = "Client does not support sampling";
+    pub const ELICITATION_UNSUPPORTED: &str = "Client does not support elicitation";
+    pub const SAMPLING_NOT_TEXT: &str = "Client model did not answer with text";
+    pub const INVALID_CURSOR: &str = "Cursor is invalid or out of range";
     pub const PATH_REQUIRED: &str = "Path parameter is required";
-    pub const COMMIT_SHA_EXTRACT_FAILED: &str = "Could not extract commit SHA";
     pub const TOOL_NOT_FOUND: &str = "Tool not found";
+    pub const PROMPT_NOT_FOUND: &str = "Prompt not found";
+    pub const PROMPT_GET_FAILED: &str = "Prompt generation failed";
+    pub const COMPLETION_FAILED: &str = "Completion failed";
+    pub const UNKNOWN_COMPLETION_REFERENCE: &str = "Unknown completion reference";
+    pub const METHOD_NOT_FOUND: &str = "Method not found";
+    pub const INVALID_MESSAGE: &str = "Message must be a JSON object";
+    pub const EMPTY_BATCH: &str = "Batch must not be empty";
+    pub const INITIALIZE_IN_BATCH: &str = "initialize must not be part of a batch";
+    pub const BATCH_NOT_SUPPORTED: &str = "Batches are not supported by the negotiated protocol version";
+    pub const METHOD_REQUIRED: &str = "Request is missing a method";
+    pub const INVALID_JSON: &str = "Invalid JSON";
+    pub const INVALID_PARAMS: &str = "Invalid params for";
+    pub const SESSION_ID_REQUIRED: &str = "Mcp-Session-Id header is required";
+    pub const SESSION_ID_QUERY_REQUIRED: &str = "sessionId query parameter is required";
+    pub const SESSION_NOT_FOUND: &str = "Session not found";
+    pub const UNSUPPORTED_PROTOCOL_VERSION: &str = "Unsupported protocol version";
+    pub const STREAM_ALREADY_OPEN: &str = "An event stream is already open for this session";
+    pub const ORIGIN_NOT_ALLOWED: &str = "Origin not allowed";
+    pub const MESSAGE_REQUIRED: &str = "Commit message must not be empty";
+    pub const TOKEN_REQUIRED: &str = "--allow-write needs a GitHub token in GITHUB_TOKEN";
+    pub const DATE_REQUIRED: &str = "Date parameter is required";
+    pub const INVALID_DATE: &str = "Date must be YYYY-MM-DD or an ISO 8601 timestamp";
+    pub const TOPIC_REQUIRED: &str = "Topic parameter is required";
+    pub const TOPIC_BRANCH_REQUIRED: &str = "Writes go to a review branch; a topic with letters or digits is required";
+    pub const DEFAULT_BRANCH_PULL_REQUEST: &str = "Pull requests must come from a branch other than main";
     pub const RESOURCE_NOT_FOUND: &str = "Resource not found";
     pub const TOOL_EXECUTION_FAILED: &str = "Tool execution failed";
     pub const RESOURCE_READ_FAILED: &str = "Resource read failed";
@@ -43,42 +195,150 @@ pub mod errors {
 // JSON-RPC error codes
 pub mod rpc_errors {
     pub const PARSE_ERROR: i32 = -32700;
+    pub const INVALID_REQUEST: i32 = -32600;
     pub const METHOD_NOT_FOUND: i32 = -32601;
+    pub const INVALID_PARAMS: i32 = -32602;
     pub const INTERNAL_ERROR: i32 = -32603;
+    // MCP-specific server error
+    pub const RESOURCE_NOT_FOUND: i32 = -32002;
 }
 
 // Schema definitions
 pub mod schemas {
     use serde_json::json;
     
-    pub fn list_files_schema() -> serde_json::Value {
+    pub fn list_files_output_schema() -> serde_json::Value {
         json!({
             "type": "object",
             "properties": {
-                "path": {
-                    "type": "string",
-                    "description": "Optional path within the repository (default: root)"
-                }
-            }
+                "files": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "name": { "type": "string" },
+                            "path": { "type": "string" },
+                            "sha": { "type": "string" },
+                            "type": { "type": "string", "enum": ["file", "dir", "symlink", "submodule"] },
+                            "size": { "type": ["integer", "null"] },
+                            "download_url": { "type": ["string", "null"] }
+                        },
+                        "required": ["name", "path", "sha", "type"]
+                    }
+                },
+                "nextCursor": { "type": "string" }
+            },
+            "required": ["files"]
         })
     }
-    
-    pub fn get_file_content_schema() -> serde_json::Value {
+
+    pub fn get_file_content_output_schema() -> serde_json::Value {
         json!({
             "type": "object",
             "properties": {
-                "path": {
-                    "type": "string",
-                    "description": "Path to the file within the repository"
-                }
+                "path": { "type": "string" },
+                "ref": { "type": ["string", "null"] },
+                "sha": { "type": "string" },
+                "size": { "type": "integer" },
+                "content": { "type": "string" }
             },
-            "required": ["path"]
+            "required": ["path", "sha", "size", "content"]
         })
     }
-    
-    pub fn get_latest_commit_schema() -> serde_json::Value {
+
+    pub fn summarize_note_output_schema() -> serde_json::Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": { "type": "string" },
+                "ref": { "type": ["string", "null"] },
+                "summary": { "type": "string" },
+                "model": { "type": "string" }
+            },
+            "required": ["path", "summary", "model"]
+        })
+    }
+
+    pub fn write_file_output_schema() -> serde_json::Value {
         json!({
-            "type": "object"
+            "type": "object",
+            "properties": {
+                "path": { "type": "string" },
+                "branch": { "type": "string" },
+                "sha": { "type": ["string", "null"] },
+                "commitSha": { "type": "string" },
+                "commitUrl": { "type": ["string", "null"] },
+                "pullRequestNumber": { "type": ["integer", "null"] },
+                "pullRequestUrl": { "type": ["string", "null"] }
+            },
+            "required": ["path", "branch", "commitSha"]
+        })
+    }
+
+    pub fn commit_changes_output_schema() -> serde_json::Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "branch": { "type": "string" },
+                "baseSha": { "type": "string" },
+                "dryRun": { "type": "boolean" },
+                "changes": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "path": { "type": "string" },
+                            "action": { "type": "string", "enum": ["add", "modify", "delete", "unchanged"] },
+                            "sha": { "type": ["string", "null"] },
+                            "newSha": { "type": ["string", "null"] }
+                        },
+                        "required": ["path", "action"]
+                    }
+                },
+                "commitSha": { "type": ["string", "null"] },
+                "commitUrl": { "type": ["string", "null"] },
+                "pullRequestNumber": { "type": ["integer", "null"] },
+                "pullRequestUrl": { "type": ["string", "null"] }
+            },
+            "required": ["branch", "baseSha", "dryRun", "changes"]
+        })
+    }
+
+    pub fn create_branch_output_schema() -> serde_json::Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "branch": { "type": "string" },
+                "sha": { "type": "string" }
+            },
+            "required": ["branch", "sha"]
+        })
+    }
+
+    pub fn pull_request_output_schema() -> serde_json::Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "branch": { "type": "string" },
+                "title": { "type": "string" },
+                "pullRequestNumber": { "type": "integer" },
+                "pullRequestUrl": { "type": "string" }
+            },
+            "required": ["branch", "title", "pullRequestNumber", "pullRequestUrl"]
+        })
+    }
+
+    pub fn get_latest_commit_output_schema() -> serde_json::Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "sha": { "type": "string" },
+                "message": { "type": "string" },
+                "author": { "type": ["string", "null"] },
+                "date": { "type": ["string", "null"] },
+                "url": { "type": ["string", "null"] }
+            },
+            "required": ["sha", "message"]
         })
     }
 }
@@ -87,4 +347,5 @@ pub mod schemas {
 pub mod mime_types {
     pub const TEXT_PLAIN: &str = "text/plain";
     pub const APPLICATION_JSON: &str = "application/json";
+    pub const TEXT_EVENT_STREAM: &str = "text/event-stream";
 }
\ No newline at end of file
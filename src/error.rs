@@ -0,0 +1,58 @@
+use crate::constants::errors;
+use std::fmt;
+
+/// Failures that handlers report to the client in a structured way.
+///
+/// Invalid arguments are protocol errors (`-32602`); every other variant is a
+/// tool execution failure the model can see and recover from.
+#[derive(Debug)]
+pub enum ToolError {
+    /// The requested file, directory, ref or resource does not exist.
+    NotFound(String),
+    /// GitHub rejected the request's credentials or lacks access to the repository.
+    Unauthorized(String),
+    /// The GitHub API rate limit is exhausted.
+    RateLimited { retry_after_secs: Option<u64> },
+    /// The file exceeds what the GitHub contents API returns inline.
+    TooLarge { path: String, size: u64 },
+    /// The file or branch changed since the state the write was based on.
+    Conflict(String),
+    /// GitHub refused the request as invalid, with its explanation.
+    Rejected { what: String, message: String },
+    /// An argument is missing or has the wrong shape.
+    InvalidArgument { name: String, message: String },
+}
+
+impl ToolError {
+    pub fn invalid_argument(name: &str, message: &str) -> Self {
+        Self::InvalidArgument {
+            name: name.to_string(),
+            message: message.to_string(),
+        }
+    }
+
+    /// Whether the error is the caller's fault and should be answered with a
+    /// JSON-RPC error rather than a failed tool result.
+    pub fn is_protocol_error(&self) -> bool {
+        matches!(self, Self::InvalidArgument { .. })
+    }
+}
+
+impl fmt::Display for ToolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotFound(what) => write!(f, "{}: {}", errors::NOT_FOUND, what),
+            Self::Unauthorized(status) => write!(f, "{} ({})", errors::UNAUTHORIZED, status),
+            Self::RateLimited {
+                retry_after_secs: Some(secs),
+            } => write!(f, "{}; retry in {} seconds", errors::RATE_LIMITED, secs),
+            Self::RateLimited { retry_after_secs: None } => write!(f, "{}", errors::RATE_LIMITED),
+            Self::Conflict(what) => write!(f, "{}: {}", errors::CONFLICT, what),
+            Self::Rejected { what, message } => write!(f, "{} ({}): {}", errors::REJECTED, what, message),
+            Self::TooLarge { path, size } => write!(f, "{}: {} ({} bytes)", errors::FILE_TOO_LARGE, path, size),
+            Self::InvalidArgument { name, message } => write!(f, "{} '{}': {}", errors::INVALID_ARGUMENT, name, message),
+        }
+    }
+}
+
+impl std::error::Error for ToolError {}
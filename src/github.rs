@@ -1,8 +1,14 @@
-use crate::constants::github as constants;
+use crate::constants::{github as constants, mcp as mcp_constants};
+use crate::error::ToolError;
+use crate::logging;
+use crate::mcp::LoggingLevel;
 use crate::utils;
 use anyhow::{anyhow, Result};
 use serde::{Deserialize, Serialize};
 use base64::Engine;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct GitHubFile {
@@ -25,19 +31,171 @@ pub struct GitHubContent {
     pub encoding: String,
 }
 
+impl GitHubContent {
+    pub fn decoded_content(&self) -> Result<String> {
+        // Files over 1 MB come back without inline content
+        if self.encoding == constants::NO_ENCODING {
+            return Err(ToolError::TooLarge {
+                path: self.path.clone(),
+                size: self.size,
+            }
+            .into());
+        }
+
+        if self.encoding == constants::BASE64_ENCODING {
+            let decoded = base64::engine::general_purpose::STANDARD
+                .decode(self.content.replace('\n', ""))?;
+            Ok(String::from_utf8(decoded)?)
+        } else {
+            Ok(self.content.clone())
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GitHubCommit {
+    pub sha: String,
+    pub commit: GitHubCommitDetail,
+    pub html_url: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GitHubCommitDetail {
+    pub message: String,
+    pub author: Option<GitHubCommitAuthor>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GitHubCommitAuthor {
+    pub name: String,
+    pub date: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GitHubTreeEntry {
+    pub path: String,
+    /// Git file mode, e.g. `100644` for a file, `100755` for an executable or `120000` for a symlink
+    pub mode: String,
+    #[serde(rename = "type")]
+    pub entry_type: String,
+    pub sha: String,
+    pub size: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GitHubTree {
+    pub sha: String,
+    pub tree: Vec<GitHubTreeEntry>,
+    pub truncated: bool,
+}
+
+impl From<GitHubTreeEntry> for GitHubFile {
+    fn from(entry: GitHubTreeEntry) -> Self {
+        let file_type = if entry.entry_type == constants::TREE_TYPE_TREE {
+            "dir"
+        } else {
+            "file"
+        };
+
+        Self {
+            name: entry.path.rsplit('/').next().unwrap_or_default().to_string(),
+            path: entry.path,
+            sha: entry.sha,
+            file_type: file_type.to_string(),
+            size: entry.size,
+            download_url: None,
+        }
+    }
+}
+
+/// Result of a contents API write: the file as written (absent after a
+/// delete) and the commit that wrote it.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GitHubContentUpdate {
+    pub content: Option<GitHubFile>,
+    pub commit: GitHubWriteCommit,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GitHubWriteCommit {
+    pub sha: String,
+    pub message: String,
+    pub html_url: Option<String>,
+}
+
+/// An entry of a tree being created. A `None` sha removes the path from
+/// the base tree.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GitHubNewTreeEntry {
+    pub path: String,
+    pub mode: String,
+    #[serde(rename = "type")]
+    pub entry_type: String,
+    pub sha: Option<String>,
+}
+
+// Git Data API responses that only matter for their SHA
+#[derive(Debug, Deserialize)]
+struct GitHubObject {
+    sha: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct GitHubGitRef {
+    object: GitHubObject,
+}
+
+#[derive(Debug, Deserialize)]
+struct GitHubGitCommit {
+    tree: GitHubObject,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GitHubPullRequest {
+    pub number: u64,
+    pub html_url: String,
+    pub title: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GitHubRef {
+    pub name: String,
+}
+
+struct CachedTree {
+    fetched_at: Instant,
+    entries: Vec<GitHubTreeEntry>,
+}
+
+// The cached default branch listing. `generation` counts writes, so a
+// listing that was being fetched while a write went through is not stored
+#[derive(Default)]
+struct TreeCache {
+    tree: Option<CachedTree>,
+    generation: u64,
+}
+
+impl TreeCache {
+    fn invalidate(&mut self) {
+        self.tree = None;
+        self.generation += 1;
+    }
+}
+
+/// Client for one repository. Clones share the cached tree listing, so a
+/// write through any clone is seen by reads through the others.
+#[derive(Clone)]
 pub struct GitHubClient {
     pub client: reqwest::Client,
     owner: String,
     repo: String,
+    token: Option<String>,
+    tree_cache: Arc<Mutex<TreeCache>>,
 }
 
 impl GitHubClient {
     pub fn new(owner: String, repo: String) -> Self {
-        Self {
-            client: reqwest::Client::new(),
-            owner,
-            repo,
-        }
+        Self::new_with_client(owner, repo, reqwest::Client::new())
     }
     
     pub fn new_default() -> Self {
@@ -49,88 +207,479 @@ impl GitHubClient {
             client,
             owner,
             repo,
+            token: None,
+            tree_cache: Arc::new(Mutex::new(TreeCache::default())),
         }
     }
 
-    pub async fn list_files(&self, path: Option<&str>) -> Result<Vec<GitHubFile>> {
-        let path = path.unwrap_or("");
-        let url = utils::build_github_contents_url(&self.owner, &self.repo, path);
-        let (header_name, header_value) = utils::get_user_agent_header();
+    /// Authenticates every request with `token`. Writes need a token with
+    /// contents permission on the repository.
+    pub fn with_token(mut self, token: String) -> Self {
+        self.token = Some(token);
+        self
+    }
 
-        let response = self
-            .client
-            .get(&url)
-            .header(header_name, header_value)
-            .send()
-            .await?;
-
-        if !response.status().is_success() {
-            return Err(anyhow!(
-                "{}: {}",
-                crate::constants::errors::GITHUB_API_FAILED,
-                response.status()
-            ));
-        }
+    pub async fn list_files(&self, path: Option<&str>, git_ref: Option<&str>) -> Result<Vec<GitHubFile>> {
+        let path = path.unwrap_or("");
+        let url = utils::with_ref_query(utils::build_github_contents_url(&self.owner, &self.repo, path), git_ref);
+        let response = self.send_get(&url, path).await?;
 
         let files: Vec<GitHubFile> = response.json().await?;
         Ok(files)
     }
 
-    pub async fn get_file_content(&self, path: &str) -> Result<String> {
+    /// Fetches a file's metadata together with its still encoded content.
+    pub async fn get_file(&self, path: &str, git_ref: Option<&str>) -> Result<GitHubContent> {
+        let url = utils::with_ref_query(utils::build_github_contents_url(&self.owner, &self.repo, path), git_ref);
+        let response = self.send_get(&url, path).await?;
+
+        let content: GitHubContent = response.json().await?;
+        Ok(content)
+    }
+
+    pub async fn get_file_content(&self, path: &str, git_ref: Option<&str>) -> Result<String> {
+        self.get_file(path, git_ref).await?.decoded_content()
+    }
+
+    pub async fn get_latest_commit(&self) -> Result<GitHubCommit> {
+        self.get_commit(constants::DEFAULT_BRANCH).await
+    }
+
+    /// The commit a branch, tag or commit SHA refers to.
+    pub async fn get_commit(&self, git_ref: &str) -> Result<GitHubCommit> {
+        let url = utils::build_github_commits_url(&self.owner, &self.repo, git_ref);
+        let response = self.send_get(&url, git_ref).await?;
+
+        let commit: GitHubCommit = response.json().await?;
+        Ok(commit)
+    }
+
+    pub async fn get_latest_commit_sha(&self) -> Result<String> {
+        Ok(self.get_latest_commit().await?.sha)
+    }
+
+    /// Commits on the default branch since `since`, a full ISO 8601 timestamp
+    /// (see `utils::normalize_github_timestamp`).
+    pub async fn list_commits_since(&self, since: &str) -> Result<Vec<GitHubCommit>> {
+        let url = utils::build_github_commits_since_url(&self.owner, &self.repo, constants::DEFAULT_BRANCH, since);
+        let response = self.send_get(&url, constants::DEFAULT_BRANCH).await?;
+
+        let commits: Vec<GitHubCommit> = response.json().await?;
+        Ok(commits)
+    }
+
+    /// Creates `path` or replaces it, committing to `branch`.
+    /// Replacing requires `sha`, the blob SHA of the file being replaced;
+    /// GitHub refuses the write with a conflict when it no longer matches.
+    pub async fn put_file(
+        &self,
+        path: &str,
+        content: &str,
+        message: &str,
+        sha: Option<&str>,
+        branch: &str,
+    ) -> Result<GitHubContentUpdate> {
         let url = utils::build_github_contents_url(&self.owner, &self.repo, path);
-        let (header_name, header_value) = utils::get_user_agent_header();
+        let mut body = serde_json::json!({
+            "message": message,
+            "content": base64::engine::general_purpose::STANDARD.encode(content),
+            "branch": branch,
+        });
+        if let Some(sha) = sha {
+            body["sha"] = serde_json::Value::String(sha.to_string());
+        }
 
-        let response = self
-            .client
-            .get(&url)
-            .header(header_name, header_value)
-            .send()
-            .await?;
-
-        if !response.status().is_success() {
-            return Err(anyhow!(
-                "{}: {}",
-                crate::constants::errors::GITHUB_API_FAILED,
-                response.status()
-            ));
+        let response = self.send_write(reqwest::Method::PUT, &url, &body, path).await?;
+        Ok(response.json().await?)
+    }
+
+    /// Deletes `path` from `branch` if its blob SHA is still `sha`.
+    pub async fn delete_file(&self, path: &str, message: &str, sha: &str, branch: &str) -> Result<GitHubContentUpdate> {
+        let url = utils::build_github_contents_url(&self.owner, &self.repo, path);
+        let body = serde_json::json!({
+            "message": message,
+            "sha": sha,
+            "branch": branch,
+        });
+
+        let response = self.send_write(reqwest::Method::DELETE, &url, &body, path).await?;
+        Ok(response.json().await?)
+    }
+
+    /// The commit SHA `branch` points at.
+    pub async fn get_branch_head(&self, branch: &str) -> Result<String> {
+        let url = utils::build_github_git_url(&self.owner, &self.repo, &format!("ref/heads/{}", utils::encode_path(branch)));
+        let response = self.send_get(&url, branch).await?;
+
+        let git_ref: GitHubGitRef = response.json().await?;
+        Ok(git_ref.object.sha)
+    }
+
+    /// The SHA of the tree a commit points at.
+    pub async fn get_commit_tree(&self, commit_sha: &str) -> Result<String> {
+        let url = utils::build_github_git_url(&self.owner, &self.repo, &format!("commits/{}", commit_sha));
+        let response = self.send_get(&url, commit_sha).await?;
+
+        let commit: GitHubGitCommit = response.json().await?;
+        Ok(commit.tree.sha)
+    }
+
+    pub async fn create_blob(&self, path: &str, content: &str) -> Result<String> {
+        let url = utils::build_github_git_url(&self.owner, &self.repo, "blobs");
+        let body = serde_json::json!({
+            "content": base64::engine::general_purpose::STANDARD.encode(content),
+            "encoding": constants::BASE64_ENCODING,
+        });
+        let response = self.send_write(reqwest::Method::POST, &url, &body, path).await?;
+
+        let blob: GitHubObject = response.json().await?;
+        Ok(blob.sha)
+    }
+
+    /// Creates a tree from `base_tree` with `entries` added, replaced or removed.
+    pub async fn create_tree(&self, base_tree: &str, entries: &[GitHubNewTreeEntry]) -> Result<String> {
+        let url = utils::build_github_git_url(&self.owner, &self.repo, "trees");
+        let body = serde_json::json!({ "base_tree": base_tree, "tree": entries });
+        let response = self.send_write(reqwest::Method::POST, &url, &body, base_tree).await?;
+
+        let tree: GitHubObject = response.json().await?;
+        Ok(tree.sha)
+    }
+
+    pub async fn create_commit(&self, message: &str, tree: &str, parent: &str) -> Result<GitHubWriteCommit> {
+        let url = utils::build_github_git_url(&self.owner, &self.repo, "commits");
+        let body = serde_json::json!({ "message": message, "tree": tree, "parents": [parent] });
+        let response = self.send_write(reqwest::Method::POST, &url, &body, tree).await?;
+
+        Ok(response.json().await?)
+    }
+
+    /// Creates `branch` pointing at `commit_sha`; fails with a conflict when
+    /// the branch already exists.
+    pub async fn create_branch(&self, branch: &str, commit_sha: &str) -> Result<()> {
+        let url = utils::build_github_git_url(&self.owner, &self.repo, "refs");
+        let body = serde_json::json!({ "ref": format!("refs/heads/{}", branch), "sha": commit_sha });
+        self.send_write(reqwest::Method::POST, &url, &body, branch).await?;
+        Ok(())
+    }
+
+    /// The open pull request from `branch`, if there is one.
+    pub async fn find_pull_request(&self, branch: &str) -> Result<Option<GitHubPullRequest>> {
+        let url = format!(
+            "{}?head={}&state=open",
+            utils::build_github_pulls_url(&self.owner, &self.repo),
+            utils::encode_url_component(&format!("{}:{}", self.owner, branch))
+        );
+        let response = self.send_get(&url, branch).await?;
+
+        let pull_requests: Vec<GitHubPullRequest> = response.json().await?;
+        Ok(pull_requests.into_iter().next())
+    }
+
+    /// Opens a pull request from `branch` into `base`. When one was opened
+    /// in the meantime, that pull request is returned instead.
+    pub async fn create_pull_request(&self, branch: &str, base: &str, title: &str, body: &str) -> Result<GitHubPullRequest> {
+        let url = utils::build_github_pulls_url(&self.owner, &self.repo);
+        let request = serde_json::json!({ "head": branch, "base": base, "title": title, "body": body });
+        let response = match self.send_write(reqwest::Method::POST, &url, &request, branch).await {
+            Err(e) if matches!(
+                e.downcast_ref::<ToolError>(),
+                Some(ToolError::Rejected { message, .. }) if message.contains(constants::PULL_REQUEST_EXISTS)
+            ) =>
+            {
+                return self.find_pull_request(branch).await?.ok_or(e);
+            }
+            result => result?,
+        };
+
+        Ok(response.json().await?)
+    }
+
+    /// Moves `branch` to `commit_sha` if that is a fast-forward; otherwise
+    /// the branch moved since the commit was built and this fails with a conflict.
+    pub async fn fast_forward_branch(&self, branch: &str, commit_sha: &str) -> Result<()> {
+        let url = utils::build_github_git_url(&self.owner, &self.repo, &format!("refs/heads/{}", utils::encode_path(branch)));
+        let body = serde_json::json!({ "sha": commit_sha, "force": false });
+        self.send_write(reqwest::Method::PATCH, &url, &body, branch).await?;
+        Ok(())
+    }
+
+    /// Returns every entry of the tree at `git_ref` (default branch when `None`).
+    /// The default branch listing is cached for `TREE_CACHE_TTL_SECS`.
+    pub async fn list_tree(&self, git_ref: Option<&str>) -> Result<Vec<GitHubTreeEntry>> {
+        self.list_tree_with_progress(git_ref, &|_, _| {}).await
+    }
+
+    /// Like `list_tree`, calling `on_progress(listed, known)` for every
+    /// directory listed when the tree is too large for one request.
+    pub async fn list_tree_with_progress(
+        &self,
+        git_ref: Option<&str>,
+        on_progress: &(dyn Fn(usize, usize) + Sync),
+    ) -> Result<Vec<GitHubTreeEntry>> {
+        if let Some(git_ref) = git_ref.filter(|git_ref| !git_ref.is_empty()) {
+            return self.fetch_tree(git_ref, on_progress).await;
         }
 
-        let content: GitHubContent = response.json().await?;
-        
-        if content.encoding == constants::BASE64_ENCODING {
-            let decoded = base64::engine::general_purpose::STANDARD
-                .decode(&content.content.replace('\n', ""))?;
-            Ok(String::from_utf8(decoded)?)
-        } else {
-            Ok(content.content)
+        let generation = {
+            let cache = self.tree_cache.lock().unwrap();
+            if let Some(cached) = cache.tree.as_ref()
+                && cached.fetched_at.elapsed() < Duration::from_secs(constants::TREE_CACHE_TTL_SECS)
+            {
+                logging::log(
+                    LoggingLevel::Debug,
+                    mcp_constants::LOGGER_GITHUB,
+                    serde_json::json!({ "message": "Tree cache hit", "ref": constants::DEFAULT_BRANCH }),
+                );
+                return Ok(cached.entries.clone());
+            }
+            cache.generation
+        };
+
+        // Fetched without holding the lock, so a slow listing does not hold
+        // up reads that the cache or another listing can answer
+        let entries = self.fetch_tree(constants::DEFAULT_BRANCH, on_progress).await?;
+        let mut cache = self.tree_cache.lock().unwrap();
+        if cache.generation == generation {
+            cache.tree = Some(CachedTree {
+                fetched_at: Instant::now(),
+                entries: entries.clone(),
+            });
         }
+
+        Ok(entries)
     }
 
-    pub async fn get_latest_commit_sha(&self) -> Result<String> {
-        let url = utils::build_github_commits_url(&self.owner, &self.repo, constants::DEFAULT_BRANCH);
+    async fn fetch_tree(&self, git_ref: &str, on_progress: &(dyn Fn(usize, usize) + Sync)) -> Result<Vec<GitHubTreeEntry>> {
+        let url = utils::build_github_tree_url(&self.owner, &self.repo, git_ref);
+        let response = self.send_get(&url, git_ref).await?;
+
+        let tree: GitHubTree = response.json().await?;
+        if !tree.truncated {
+            return Ok(tree.tree);
+        }
+
+        // GitHub cuts recursive listings of large trees short; list them one
+        // directory at a time instead
+        logging::log(
+            LoggingLevel::Warning,
+            mcp_constants::LOGGER_GITHUB,
+            serde_json::json!({ "message": "Recursive tree listing truncated; walking directories", "ref": git_ref }),
+        );
+        let tree_url = |sha: &str| utils::build_github_git_url(&self.owner, &self.repo, &format!("trees/{}", sha));
+        self.walk_tree(&tree.sha, &tree_url, on_progress).await
+    }
+
+    // Lists the tree `root_sha` breadth first with one non-recursive request
+    // per directory, reporting directories listed against directories found
+    async fn walk_tree(
+        &self,
+        root_sha: &str,
+        tree_url: &(dyn Fn(&str) -> String + Sync),
+        on_progress: &(dyn Fn(usize, usize) + Sync),
+    ) -> Result<Vec<GitHubTreeEntry>> {
+        let mut entries = Vec::new();
+        let mut pending = VecDeque::from([(String::new(), root_sha.to_string())]);
+        let mut listed = 0;
+
+        while let Some((prefix, sha)) = pending.pop_front() {
+            let what = if prefix.is_empty() { sha.as_str() } else { prefix.as_str() };
+            let tree: GitHubTree = self.send_get(&tree_url(&sha), what).await?.json().await?;
+            for mut entry in tree.tree {
+                if !prefix.is_empty() {
+                    entry.path = format!("{}/{}", prefix, entry.path);
+                }
+                if entry.entry_type == constants::TREE_TYPE_TREE {
+                    pending.push_back((entry.path.clone(), entry.sha.clone()));
+                }
+                entries.push(entry);
+            }
+
+            listed += 1;
+            on_progress(listed, listed + pending.len());
+        }
+
+        Ok(entries)
+    }
+
+    // Sends a GET request, retrying connection errors and 5xx responses up to
+    // `MAX_RETRIES` times, and maps the final failure with `check_response`
+    async fn send_get(&self, url: &str, what: &str) -> Result<reqwest::Response> {
+        let mut attempt = 0;
+
+        loop {
+            let started = Instant::now();
+            let result = self.request(reqwest::Method::GET, url).send().await;
+            let retryable = match &result {
+                Ok(response) => response.status().is_server_error(),
+                Err(e) => e.is_connect() || e.is_timeout(),
+            };
+            logging::log(
+                LoggingLevel::Debug,
+                mcp_constants::LOGGER_GITHUB,
+                serde_json::json!({
+                    "method": "GET",
+                    "url": url,
+                    "status": result.as_ref().ok().map(|response| response.status().as_u16()),
+                    "elapsedMs": started.elapsed().as_millis() as u64,
+                }),
+            );
+
+            if retryable && attempt < constants::MAX_RETRIES {
+                attempt += 1;
+                logging::log(
+                    LoggingLevel::Warning,
+                    mcp_constants::LOGGER_GITHUB,
+                    serde_json::json!({ "message": "Retrying GitHub request", "url": url, "attempt": attempt }),
+                );
+                tokio::time::sleep(Duration::from_millis(constants::RETRY_BACKOFF_MS * u64::from(attempt))).await;
+                continue;
+            }
+
+            let result = match result {
+                Ok(response) => check_response(response, what).await,
+                Err(e) => Err(e.into()),
+            };
+            return result.inspect_err(|e| {
+                logging::log(
+                    LoggingLevel::Error,
+                    mcp_constants::LOGGER_GITHUB,
+                    serde_json::json!({ "message": "GitHub request failed", "url": url, "error": e.to_string() }),
+                );
+            });
+        }
+    }
+
+    // Sends a write with a JSON body. Writes are not retried, since a
+    // request that timed out may still have been applied; a successful write
+    // drops the cached tree listing.
+    async fn send_write(
+        &self,
+        method: reqwest::Method,
+        url: &str,
+        body: &serde_json::Value,
+        what: &str,
+    ) -> Result<reqwest::Response> {
+        let started = Instant::now();
+        let result = self.request(method.clone(), url).json(body).send().await;
+        logging::log(
+            LoggingLevel::Info,
+            mcp_constants::LOGGER_GITHUB,
+            serde_json::json!({
+                "method": method.as_str(),
+                "url": url,
+                "status": result.as_ref().ok().map(|response| response.status().as_u16()),
+                "elapsedMs": started.elapsed().as_millis() as u64,
+            }),
+        );
+
+        let result = match result {
+            Ok(response) => check_response(response, what).await,
+            Err(e) => Err(e.into()),
+        };
+        let response = result.inspect_err(|e| {
+            logging::log(
+                LoggingLevel::Error,
+                mcp_constants::LOGGER_GITHUB,
+                serde_json::json!({ "message": "GitHub write failed", "url": url, "error": e.to_string() }),
+            );
+        })?;
+        self.tree_cache.lock().unwrap().invalidate();
+        Ok(response)
+    }
+
+    fn request(&self, method: reqwest::Method, url: &str) -> reqwest::RequestBuilder {
         let (header_name, header_value) = utils::get_user_agent_header();
+        let request = self.client.request(method, url).header(header_name, header_value);
+        match &self.token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
 
-        let response = self
-            .client
-            .get(&url)
-            .header(header_name, header_value)
-            .send()
-            .await?;
-
-        if !response.status().is_success() {
-            return Err(anyhow!(
-                "{}: {}",
-                crate::constants::errors::GITHUB_API_FAILED,
-                response.status()
-            ));
+    pub async fn list_branches(&self) -> Result<Vec<String>> {
+        self.list_ref_names(&utils::build_github_branches_url(&self.owner, &self.repo))
+            .await
+    }
+
+    pub async fn list_tags(&self) -> Result<Vec<String>> {
+        self.list_ref_names(&utils::build_github_tags_url(&self.owner, &self.repo))
+            .await
+    }
+
+    async fn list_ref_names(&self, url: &str) -> Result<Vec<String>> {
+        let response = self.send_get(url, url).await?;
+
+        let refs: Vec<GitHubRef> = response.json().await?;
+        Ok(refs.into_iter().map(|r| r.name).collect())
+    }
+}
+
+// Maps unsuccessful GitHub responses to typed errors; `what` names the
+// requested path or ref in the error message
+async fn check_response(response: reqwest::Response, what: &str) -> Result<reqwest::Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+
+    let header = |name: &str| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<u64>().ok())
+    };
+    let rate_limited = status == reqwest::StatusCode::TOO_MANY_REQUESTS
+        || (status == reqwest::StatusCode::FORBIDDEN && header(constants::RATE_LIMIT_REMAINING_HEADER) == Some(0));
+
+    if rate_limited {
+        let retry_after_secs = header(constants::RETRY_AFTER_HEADER).or_else(|| {
+            let reset = header(constants::RATE_LIMIT_RESET_HEADER)?;
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs();
+            Some(reset.saturating_sub(now))
+        });
+        return Err(ToolError::RateLimited { retry_after_secs }.into());
+    }
+
+    match status {
+        reqwest::StatusCode::NOT_FOUND => Err(ToolError::NotFound(what.to_string()).into()),
+        reqwest::StatusCode::CONFLICT => Err(ToolError::Conflict(what.to_string()).into()),
+        // GitHub answers 422 both to writes whose base is gone, such as a ref
+        // update that is no longer a fast-forward, and to invalid requests
+        reqwest::StatusCode::UNPROCESSABLE_ENTITY => {
+            let message = validation_message(&response.text().await.unwrap_or_default());
+            if constants::STALE_WRITE_MESSAGES.iter().any(|stale| message.contains(stale)) {
+                Err(ToolError::Conflict(what.to_string()).into())
+            } else {
+                Err(ToolError::Rejected { what: what.to_string(), message }.into())
+            }
+        }
+        reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN => {
+            Err(ToolError::Unauthorized(status.to_string()).into())
         }
+        _ => Err(anyhow!("{}: {}", crate::constants::errors::GITHUB_API_FAILED, status)),
+    }
+}
 
-        let commit: serde_json::Value = response.json().await?;
-        let sha = commit["sha"]
-            .as_str()
-            .ok_or_else(|| anyhow!(crate::constants::errors::COMMIT_SHA_EXTRACT_FAILED))?;
-        
-        Ok(sha.to_string())
+// Joins GitHub's error `message` with the messages of its `errors` entries,
+// e.g. "Validation Failed: A pull request already exists for owner:branch."
+fn validation_message(body: &str) -> String {
+    let Ok(body) = serde_json::from_str::<serde_json::Value>(body) else {
+        return body.to_string();
+    };
+    let message = body["message"].as_str().unwrap_or_default();
+    let details: Vec<&str> = body["errors"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|error| error.as_str().or_else(|| error["message"].as_str()))
+        .collect();
+
+    if details.is_empty() {
+        message.to_string()
+    } else {
+        format!("{}: {}", message, details.join("; "))
     }
 }
 
@@ -138,7 +687,7 @@ impl GitHubClient {
 mod tests {
     use super::*;
     use wiremock::{MockServer, Mock, ResponseTemplate};
-    use wiremock::matchers::{method, path, header};
+    use wiremock::matchers::{method, path, header, query_param};
 
     #[tokio::test]
     async fn test_list_files_success() {
@@ -234,7 +783,7 @@ mod tests {
         
         if file_content.encoding == "base64" {
             let decoded = base64::engine::general_purpose::STANDARD
-                .decode(&file_content.content.replace('\n', ""))
+                .decode(file_content.content.replace('\n', ""))
                 .unwrap();
             let decoded_string = String::from_utf8(decoded).unwrap();
             assert_eq!(decoded_string, "Hello, World!");
@@ -290,4 +839,277 @@ mod tests {
         assert_eq!(client.owner, "owner");
         assert_eq!(client.repo, "repo");
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_tree_deserialization() {
+        let mock_server = MockServer::start().await;
+
+        let mock_response = serde_json::json!({
+            "sha": "tree123",
+            "tree": [
+                { "path": "notes", "mode": "040000", "type": "tree", "sha": "a1" },
+                { "path": "notes/meeting.md", "mode": "100644", "type": "blob", "sha": "b2", "size": 42 }
+            ],
+            "truncated": false
+        });
+
+        Mock::given(method("GET"))
+            .and(path("/repos/test-owner/test-repo/git/trees/main"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(&mock_response))
+            .mount(&mock_server)
+            .await;
+
+        let url = format!("{}/repos/test-owner/test-repo/git/trees/main?recursive=1", mock_server.uri());
+        let tree: GitHubTree = reqwest::get(&url).await.unwrap().json().await.unwrap();
+
+        assert_eq!(tree.tree.len(), 2);
+        assert_eq!(tree.tree[1].entry_type, "blob");
+        assert_eq!(tree.tree[1].size, Some(42));
+    }
+
+    #[tokio::test]
+    async fn test_truncated_tree_is_walked_per_directory() {
+        let mock_server = MockServer::start().await;
+        let trees = [
+            ("root", serde_json::json!([
+                { "path": "notes", "mode": "040000", "type": "tree", "sha": "notes" },
+                { "path": "README.md", "mode": "100644", "type": "blob", "sha": "b1", "size": 10 }
+            ])),
+            ("notes", serde_json::json!([
+                { "path": "2025", "mode": "040000", "type": "tree", "sha": "year" },
+                { "path": "meeting.md", "mode": "100644", "type": "blob", "sha": "b2", "size": 42 }
+            ])),
+            ("year", serde_json::json!([{ "path": "day.md", "mode": "100644", "type": "blob", "sha": "b3", "size": 7 }])),
+        ];
+        for (sha, tree) in trees {
+            Mock::given(method("GET"))
+                .and(path(format!("/trees/{}", sha)))
+                .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({ "sha": sha, "tree": tree, "truncated": false })))
+                .expect(1)
+                .mount(&mock_server)
+                .await;
+        }
+
+        let client = GitHubClient::new("owner".to_string(), "repo".to_string());
+        let tree_url = |sha: &str| format!("{}/trees/{}", mock_server.uri(), sha);
+        let progress = std::sync::Mutex::new(Vec::new());
+        let entries = client
+            .walk_tree("root", &tree_url, &|listed, known| progress.lock().unwrap().push((listed, known)))
+            .await
+            .unwrap();
+
+        let paths: Vec<_> = entries.iter().map(|entry| entry.path.as_str()).collect();
+        assert_eq!(paths, vec!["notes", "README.md", "notes/2025", "notes/meeting.md", "notes/2025/day.md"]);
+        assert_eq!(*progress.lock().unwrap(), vec![(1, 2), (2, 3), (3, 3)]);
+    }
+
+    #[tokio::test]
+    async fn test_error_statuses_map_to_tool_errors() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path("/missing"))
+            .respond_with(ResponseTemplate::new(404))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/limited"))
+            .respond_with(
+                ResponseTemplate::new(403)
+                    .insert_header("x-ratelimit-remaining", "0")
+                    .insert_header("retry-after", "60"),
+            )
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/private"))
+            .respond_with(ResponseTemplate::new(401))
+            .mount(&mock_server)
+            .await;
+
+        let client = reqwest::Client::new();
+        let fetch = |route: &str| client.get(format!("{}{}", mock_server.uri(), route)).send();
+
+        let error = check_response(fetch("/missing").await.unwrap(), "notes/a.md").await.unwrap_err();
+        assert!(matches!(error.downcast_ref::<ToolError>(), Some(ToolError::NotFound(what)) if what == "notes/a.md"));
+
+        let error = check_response(fetch("/limited").await.unwrap(), "main").await.unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<ToolError>(),
+            Some(ToolError::RateLimited { retry_after_secs: Some(60) })
+        ));
+
+        let error = check_response(fetch("/private").await.unwrap(), "main").await.unwrap_err();
+        assert!(matches!(error.downcast_ref::<ToolError>(), Some(ToolError::Unauthorized(_))));
+    }
+
+    #[tokio::test]
+    async fn test_transient_failures_are_retried() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/flaky"))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(1)
+            .with_priority(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/flaky"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([])))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/down"))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(u64::from(constants::MAX_RETRIES) + 1)
+            .mount(&mock_server)
+            .await;
+
+        let client = GitHubClient::new("owner".to_string(), "repo".to_string());
+        let response = client.send_get(&format!("{}/flaky", mock_server.uri()), "flaky").await.unwrap();
+        assert!(response.status().is_success());
+
+        let error = client.send_get(&format!("{}/down", mock_server.uri()), "down").await.unwrap_err();
+        assert!(error.to_string().contains("500"));
+    }
+
+    #[tokio::test]
+    async fn test_paths_and_refs_are_percent_encoded() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/contents/notes/Meeting%20%233%20%3F%25.md"))
+            .and(query_param("ref", "notes/a+b"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "name": "Meeting #3 ?%.md",
+                "path": "notes/Meeting #3 ?%.md",
+                "sha": "abc123",
+                "size": 0,
+                "type": "file",
+                "content": "",
+                "encoding": "base64"
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let client = GitHubClient::new("owner".to_string(), "repo".to_string());
+        let url = utils::with_ref_query(
+            utils::build_github_contents_url("owner", "repo", "notes/Meeting #3 ?%.md"),
+            Some("notes/a+b"),
+        )
+        .replace(constants::API_BASE_URL, &mock_server.uri());
+
+        let content: GitHubContent = client.send_get(&url, "notes/Meeting #3 ?%.md").await.unwrap().json().await.unwrap();
+        assert_eq!(content.path, "notes/Meeting #3 ?%.md");
+    }
+
+    #[tokio::test]
+    async fn test_writes_are_authenticated_and_report_conflicts() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("PUT"))
+            .and(path("/repos/owner/repo/contents/notes/a.md"))
+            .and(header("Authorization", "Bearer secret"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "content": { "name": "a.md", "path": "notes/a.md", "sha": "new123", "type": "file" },
+                "commit": { "sha": "commit123", "message": "Add a", "html_url": null }
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("DELETE"))
+            .and(path("/repos/owner/repo/contents/notes/a.md"))
+            .respond_with(ResponseTemplate::new(409))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let client = GitHubClient::new("owner".to_string(), "repo".to_string()).with_token("secret".to_string());
+        let url = format!("{}/repos/owner/repo/contents/notes/a.md", mock_server.uri());
+        let body = serde_json::json!({ "message": "Add a", "content": "" });
+
+        let response = client.send_write(reqwest::Method::PUT, &url, &body, "notes/a.md").await.unwrap();
+        let update: GitHubContentUpdate = response.json().await.unwrap();
+        assert_eq!(update.commit.sha, "commit123");
+        assert_eq!(update.content.unwrap().sha, "new123");
+
+        let error = client.send_write(reqwest::Method::DELETE, &url, &body, "notes/a.md").await.unwrap_err();
+        assert!(matches!(error.downcast_ref::<ToolError>(), Some(ToolError::Conflict(_))));
+    }
+
+    #[tokio::test]
+    async fn test_validation_failures_are_not_conflicts() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("PATCH"))
+            .respond_with(ResponseTemplate::new(422).set_body_json(serde_json::json!({
+                "message": "Update is not a fast forward"
+            })))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(422).set_body_json(serde_json::json!({
+                "message": "Validation Failed",
+                "errors": [{ "resource": "PullRequest", "code": "custom", "message": "A pull request already exists for owner:notes/a." }]
+            })))
+            .mount(&mock_server)
+            .await;
+
+        let client = GitHubClient::new("owner".to_string(), "repo".to_string()).with_token("secret".to_string());
+        let body = serde_json::json!({});
+
+        let url = format!("{}/repos/owner/repo/git/refs/heads/notes/a", mock_server.uri());
+        let error = client.send_write(reqwest::Method::PATCH, &url, &body, "notes/a").await.unwrap_err();
+        assert!(matches!(error.downcast_ref::<ToolError>(), Some(ToolError::Conflict(_))));
+
+        let url = format!("{}/repos/owner/repo/pulls", mock_server.uri());
+        let error = client.send_write(reqwest::Method::POST, &url, &body, "notes/a").await.unwrap_err();
+        match error.downcast_ref::<ToolError>() {
+            Some(ToolError::Rejected { message, .. }) => {
+                assert_eq!(message, "Validation Failed: A pull request already exists for owner:notes/a.");
+                assert!(message.contains(constants::PULL_REQUEST_EXISTS));
+            }
+            other => panic!("expected a rejection, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_writes_clear_the_tree_cache_of_every_clone() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("DELETE"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "content": null,
+                "commit": { "sha": "commit123", "message": "Remove a", "html_url": null }
+            })))
+            .mount(&mock_server)
+            .await;
+
+        let reader = GitHubClient::new("owner".to_string(), "repo".to_string());
+        let writer = reader.clone().with_token("secret".to_string());
+        reader.tree_cache.lock().unwrap().tree = Some(CachedTree {
+            fetched_at: Instant::now(),
+            entries: Vec::new(),
+        });
+
+        let url = format!("{}/repos/owner/repo/contents/notes/a.md", mock_server.uri());
+        let body = serde_json::json!({ "message": "Remove a", "sha": "abc123" });
+        writer.send_write(reqwest::Method::DELETE, &url, &body, "notes/a.md").await.unwrap();
+
+        let cache = reader.tree_cache.lock().unwrap();
+        assert!(cache.tree.is_none());
+        assert_eq!(cache.generation, 1);
+    }
+
+    #[test]
+    fn test_large_file_content_is_too_large() {
+        let content = GitHubContent {
+            name: "big.pdf".to_string(),
+            path: "assets/big.pdf".to_string(),
+            sha: "abc123".to_string(),
+            size: 5_000_000,
+            content: String::new(),
+            encoding: "none".to_string(),
+        };
+
+        let error = content.decoded_content().unwrap_err();
+        assert!(matches!(error.downcast_ref::<ToolError>(), Some(ToolError::TooLarge { size: 5_000_000, .. })));
+    }
+}
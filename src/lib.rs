@@ -1,6 +1,12 @@
+pub mod local;
+pub mod logging;
 pub mod mcp;
+pub mod middleware;
 pub mod server;
+pub mod sse;
+pub mod streamable_http;
 pub mod github;
 pub mod handlers;
 pub mod constants;
+pub mod error;
 pub mod utils;
\ No newline at end of file
@@ -0,0 +1,278 @@
+use crate::constants::github as constants;
+use crate::error::ToolError;
+use crate::github::{GitHubContent, GitHubFile};
+use anyhow::Result;
+use base64::Engine;
+use sha1::{Digest, Sha1};
+use std::path::{Component, Path, PathBuf};
+
+/// A local clone of the notes repository, found through the client's roots.
+/// It serves the working tree, so it only stands in for the default branch.
+#[derive(Debug, Clone)]
+pub struct LocalRepository {
+    root: PathBuf,
+}
+
+impl LocalRepository {
+    /// Returns the repository at `root` when one of its remotes points at
+    /// `owner/repo` on GitHub.
+    pub fn detect(root: &Path, owner: &str, repo: &str) -> Option<Self> {
+        let config = std::fs::read_to_string(root.join(".git").join("config")).ok()?;
+        let expected = format!("{}/{}", owner, repo).to_lowercase();
+
+        config
+            .lines()
+            .filter_map(|line| line.trim().strip_prefix("url"))
+            .filter_map(|line| line.trim_start().strip_prefix('='))
+            .any(|url| remote_matches(url.trim(), &expected))
+            .then(|| Self {
+                root: root.to_path_buf(),
+            })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Lists one directory level, like the GitHub contents API.
+    pub async fn list_files(&self, path: Option<&str>) -> Result<Vec<GitHubFile>> {
+        let relative = path.unwrap_or_default().trim_matches('/');
+        let mut files = self.read_dir(relative).await?;
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(files)
+    }
+
+    /// Lists every file and directory below `path`. Symbolic links are
+    /// skipped, so the walk stays inside the clone and always ends.
+    pub async fn list_files_recursive(&self, path: Option<&str>) -> Result<Vec<GitHubFile>> {
+        let mut pending = vec![path.unwrap_or_default().trim_matches('/').to_string()];
+        let mut files = Vec::new();
+
+        while let Some(directory) = pending.pop() {
+            for file in self.read_dir(&directory).await? {
+                if file.file_type == "dir" {
+                    pending.push(file.path.clone());
+                }
+                files.push(file);
+            }
+        }
+
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(files)
+    }
+
+    /// Reads a file in the same shape the GitHub contents API returns it.
+    pub async fn get_file(&self, path: &str) -> Result<GitHubContent> {
+        let relative = path.trim_matches('/');
+        let bytes = tokio::fs::read(self.resolve(relative).await?).await?;
+
+        Ok(GitHubContent {
+            name: file_name(relative),
+            path: relative.to_string(),
+            sha: blob_sha(&bytes),
+            size: bytes.len() as u64,
+            content: base64::engine::general_purpose::STANDARD.encode(&bytes),
+            encoding: constants::BASE64_ENCODING.to_string(),
+        })
+    }
+
+    // Lists one directory. Files carry no `sha`: hashing every file on every
+    // listing is too slow, so the SHA is computed when a file is read.
+    async fn read_dir(&self, relative: &str) -> Result<Vec<GitHubFile>> {
+        let mut entries = tokio::fs::read_dir(self.resolve(relative).await?).await?;
+
+        let mut files = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if name == ".git" {
+                continue;
+            }
+
+            let path = if relative.is_empty() {
+                name.clone()
+            } else {
+                format!("{}/{}", relative, name)
+            };
+            // Links could point out of the clone or back up the tree
+            let file_type = entry.file_type().await?;
+            if file_type.is_symlink() {
+                continue;
+            }
+
+            let file = if file_type.is_dir() {
+                GitHubFile {
+                    name,
+                    path,
+                    sha: String::new(),
+                    file_type: "dir".to_string(),
+                    size: None,
+                    download_url: None,
+                }
+            } else {
+                GitHubFile {
+                    name,
+                    path,
+                    sha: String::new(),
+                    file_type: "file".to_string(),
+                    size: Some(entry.metadata().await?.len()),
+                    download_url: None,
+                }
+            };
+            files.push(file);
+        }
+
+        Ok(files)
+    }
+
+    // Maps a repository path to the existing file or directory in the clone,
+    // refusing paths that leave it, whether by `..` or through a symbolic link,
+    // and paths into `.git`, which listings hide and which may hold credentials
+    async fn resolve(&self, relative: &str) -> Result<PathBuf> {
+        let outside = || {
+            ToolError::invalid_argument(
+                crate::constants::mcp::PARAM_PATH,
+                "must be a relative path inside the repository",
+            )
+        };
+        if Path::new(relative)
+            .components()
+            .any(|component| !matches!(component, Component::Normal(_)))
+        {
+            return Err(outside().into());
+        }
+
+        let resolved = match tokio::fs::canonicalize(self.root.join(relative)).await {
+            Ok(resolved) => resolved,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Err(ToolError::NotFound(relative.to_string()).into());
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let root = tokio::fs::canonicalize(&self.root).await?;
+        let Ok(inside) = resolved.strip_prefix(&root) else {
+            return Err(outside().into());
+        };
+        if inside.components().next() == Some(Component::Normal(".git".as_ref())) {
+            return Err(ToolError::invalid_argument(
+                crate::constants::mcp::PARAM_PATH,
+                "must not point into the .git directory",
+            )
+            .into());
+        }
+        Ok(resolved)
+    }
+}
+
+// Accepts https and ssh remote URLs, with or without the .git suffix
+fn remote_matches(url: &str, expected: &str) -> bool {
+    let url = url.trim_end_matches('/').to_lowercase();
+    let url = url.strip_suffix(".git").unwrap_or(&url);
+    url.ends_with(&format!("github.com/{}", expected)) || url.ends_with(&format!("github.com:{}", expected))
+}
+
+fn file_name(path: &str) -> String {
+    path.rsplit('/').next().unwrap_or(path).to_string()
+}
+
+// Git's blob object ID, which matches the `sha` GitHub reports for the same content
+pub(crate) fn blob_sha(bytes: &[u8]) -> String {
+    let mut hasher = Sha1::new();
+    hasher.update(format!("blob {}\0", bytes.len()).as_bytes());
+    hasher.update(bytes);
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+// Creates a throwaway clone in the temp directory whose origin is `remote`,
+// holding `files` as (path, content) pairs
+#[cfg(test)]
+pub(crate) fn create_test_clone(remote: &str, files: &[(&str, &str)]) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("notes-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(root.join(".git")).unwrap();
+    std::fs::write(
+        root.join(".git").join("config"),
+        format!("[remote \"origin\"]\n\turl = {}\n\tfetch = +refs/heads/*:refs/remotes/origin/*\n", remote),
+    )
+    .unwrap();
+    for (path, content) in files {
+        let path = root.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+    root
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_clone(remote: &str) -> PathBuf {
+        create_test_clone(remote, &[("README.md", "hello\n"), ("daily/2025-01-01.md", "# Day one\n")])
+    }
+
+    #[tokio::test]
+    async fn test_detects_clone_and_reads_working_tree() {
+        let root = create_clone("git@github.com:ParkJong-Hun/my-notion.git");
+        assert!(LocalRepository::detect(&root, "parkjong-hun", "other").is_none());
+        let local = LocalRepository::detect(&root, "ParkJong-Hun", "my-notion").unwrap();
+
+        let files = local.list_files(None).await.unwrap();
+        let paths: Vec<_> = files.iter().map(|file| file.path.as_str()).collect();
+        assert_eq!(paths, ["README.md", "daily"]);
+
+        let files = local.list_files_recursive(None).await.unwrap();
+        assert!(files.iter().any(|file| file.path == "daily/2025-01-01.md"));
+
+        let file = local.get_file("README.md").await.unwrap();
+        assert_eq!(file.decoded_content().unwrap(), "hello\n");
+        // `git hash-object` of "hello\n"
+        assert_eq!(file.sha, "ce013625030ba8dba906f756967f9e9ca394464a");
+
+        let error = local.get_file("../outside.md").await.unwrap_err();
+        assert!(error.downcast_ref::<ToolError>().unwrap().is_protocol_error());
+        let error = local.get_file("missing.md").await.unwrap_err();
+        assert!(matches!(error.downcast_ref::<ToolError>(), Some(ToolError::NotFound(_))));
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_git_directory_is_not_readable() {
+        let root = create_clone("https://github.com/ParkJong-Hun/my-notion.git");
+        let local = LocalRepository::detect(&root, "ParkJong-Hun", "my-notion").unwrap();
+
+        for path in [".git/config", ".git"] {
+            let error = local.get_file(path).await.unwrap_err();
+            assert!(error.downcast_ref::<ToolError>().unwrap().is_protocol_error(), "{}", path);
+        }
+        assert!(local.list_files(Some(".git")).await.is_err());
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_symbolic_links_stay_inside_the_clone() {
+        let root = create_clone("https://github.com/ParkJong-Hun/my-notion.git");
+        let outside = create_test_clone("https://example.com/other.git", &[("secret.md", "secret\n")]);
+        std::os::unix::fs::symlink(&outside, root.join("elsewhere")).unwrap();
+        std::os::unix::fs::symlink(&root, root.join("daily").join("loop")).unwrap();
+        let local = LocalRepository::detect(&root, "ParkJong-Hun", "my-notion").unwrap();
+
+        let files = local.list_files_recursive(None).await.unwrap();
+        let paths: Vec<_> = files.iter().map(|file| file.path.as_str()).collect();
+        assert_eq!(paths, ["README.md", "daily", "daily/2025-01-01.md"]);
+
+        let error = local.get_file("elsewhere/secret.md").await.unwrap_err();
+        assert!(error.downcast_ref::<ToolError>().unwrap().is_protocol_error());
+        assert!(local.get_file("daily/loop/README.md").await.is_ok());
+        let error = local.get_file("daily/loop/.git/config").await.unwrap_err();
+        assert!(error.downcast_ref::<ToolError>().unwrap().is_protocol_error());
+
+        std::fs::remove_dir_all(root).unwrap();
+        std::fs::remove_dir_all(outside).unwrap();
+    }
+}
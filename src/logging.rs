@@ -0,0 +1,158 @@
+use crate::mcp::{LoggingLevel, LoggingMessageParams, ServerNotification};
+use crate::server::Peer;
+use anyhow::Result;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+/// Where local log lines are written.
+#[derive(Debug, Clone, Default)]
+pub enum LogTarget {
+    #[default]
+    Stderr,
+    /// Appends to the file, creating it if needed
+    File(PathBuf),
+}
+
+// Local sink; until `init` runs, messages at info and above go to stderr
+struct LocalSink {
+    level: LoggingLevel,
+    writer: Box<dyn Write + Send>,
+}
+
+/// The client of the request being handled and the level it asked for with
+/// `logging/setLevel`. Nothing is sent until the client sets a level.
+#[derive(Clone)]
+pub(crate) struct ClientSink {
+    peer: Peer,
+    level: Arc<Mutex<Option<LoggingLevel>>>,
+}
+
+impl ClientSink {
+    pub(crate) fn new(peer: Peer, level: Arc<Mutex<Option<LoggingLevel>>>) -> Self {
+        Self { peer, level }
+    }
+}
+
+static LOCAL: Mutex<Option<LocalSink>> = Mutex::new(None);
+
+tokio::task_local! {
+    // Set while a session's request is handled, so that its messages only
+    // reach the client that caused them
+    static CLIENT: ClientSink;
+}
+
+/// Configures the local sink: messages at `level` and above are written to `target`.
+pub fn init(level: LoggingLevel, target: LogTarget) -> Result<()> {
+    let writer: Box<dyn Write + Send> = match target {
+        LogTarget::Stderr => Box::new(std::io::stderr()),
+        LogTarget::File(path) => Box::new(OpenOptions::new().create(true).append(true).open(path)?),
+    };
+    *LOCAL.lock().unwrap() = Some(LocalSink { level, writer });
+    Ok(())
+}
+
+/// Records a message from `logger` locally and, while a request is being
+/// handled, sends it as `notifications/message` to that request's client if
+/// its level admits it.
+pub fn log(level: LoggingLevel, logger: &str, data: serde_json::Value) {
+    write_local(level, logger, &data);
+
+    let _ = CLIENT.try_with(|client| {
+        if !client.level.lock().unwrap().is_some_and(|minimum| level >= minimum) {
+            return;
+        }
+        client.peer.send_notification(&ServerNotification::LogMessage {
+            jsonrpc: "2.0".to_string(),
+            params: LoggingMessageParams {
+                level,
+                logger: Some(logger.to_string()),
+                data,
+            },
+        });
+    });
+}
+
+// Runs `future` with `client` receiving the messages it logs
+pub(crate) async fn scope<F: Future>(client: ClientSink, future: F) -> F::Output {
+    CLIENT.scope(client, future).await
+}
+
+fn write_local(level: LoggingLevel, logger: &str, data: &serde_json::Value) {
+    let text = match data {
+        serde_json::Value::String(text) => text.clone(),
+        data => data.to_string(),
+    };
+
+    let mut local = LOCAL.lock().unwrap();
+    match local.as_mut() {
+        Some(sink) if level >= sink.level => {
+            let _ = writeln!(sink.writer, "[{}] {}: {}", level.as_str(), logger, text);
+            let _ = sink.writer.flush();
+        }
+        Some(_) => {}
+        None if level >= LoggingLevel::Info => eprintln!("[{}] {}: {}", level.as_str(), logger, text),
+        None => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::mpsc;
+
+    // Other tests may log concurrently, so only messages from `logger` count
+    fn received(receiver: &mut mpsc::UnboundedReceiver<String>, logger: &str) -> Vec<serde_json::Value> {
+        std::iter::from_fn(|| receiver.try_recv().ok())
+            .map(|message| serde_json::from_str::<serde_json::Value>(&message).unwrap())
+            .filter(|message| message["params"]["logger"] == logger)
+            .collect()
+    }
+
+    fn create_client(level: Option<LoggingLevel>) -> (ClientSink, Arc<Mutex<Option<LoggingLevel>>>, mpsc::UnboundedReceiver<String>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let level = Arc::new(Mutex::new(level));
+        (ClientSink::new(Peer::new(sender), Arc::clone(&level)), level, receiver)
+    }
+
+    #[tokio::test]
+    async fn test_clients_receive_messages_at_or_above_their_level() {
+        let (client, level, mut receiver) = create_client(None);
+
+        scope(client.clone(), async {
+            log(LoggingLevel::Error, "level-test", serde_json::json!({ "before": "setLevel" }));
+        })
+        .await;
+        assert!(received(&mut receiver, "level-test").is_empty());
+
+        *level.lock().unwrap() = Some(LoggingLevel::Warning);
+        scope(client, async {
+            log(LoggingLevel::Info, "level-test", serde_json::json!("too quiet"));
+            log(LoggingLevel::Error, "level-test", serde_json::json!({ "status": 500 }));
+        })
+        .await;
+
+        let messages = received(&mut receiver, "level-test");
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0]["method"], "notifications/message");
+        assert_eq!(messages[0]["params"]["level"], "error");
+        assert_eq!(messages[0]["params"]["data"]["status"], 500);
+    }
+
+    #[tokio::test]
+    async fn test_messages_only_reach_the_session_that_caused_them() {
+        let (first, _, mut first_receiver) = create_client(Some(LoggingLevel::Debug));
+        let (_second, _, mut second_receiver) = create_client(Some(LoggingLevel::Debug));
+
+        scope(first, async {
+            log(LoggingLevel::Info, "session-test", serde_json::json!({ "arguments": "private" }));
+        })
+        .await;
+        log(LoggingLevel::Info, "session-test", serde_json::json!("outside any request"));
+
+        assert_eq!(received(&mut first_receiver, "session-test").len(), 1);
+        assert!(received(&mut second_receiver, "session-test").is_empty());
+    }
+}
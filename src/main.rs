@@ -1,32 +1,186 @@
-mod mcp;
-mod server;
-mod github;
-mod handlers;
-mod constants;
-mod utils;
-
 use anyhow::Result;
-use clap::Parser;
-use server::McpServer;
-use handlers::*;
-use utils::*;
+use clap::{Parser, ValueEnum};
+use get_my_notion_mcp::constants::{errors, github as github_constants, http as http_constants, mcp as mcp_constants};
+use get_my_notion_mcp::github::GitHubClient;
+use get_my_notion_mcp::handlers::{self, *};
+use get_my_notion_mcp::logging::{self, LogTarget};
+use get_my_notion_mcp::mcp::LoggingLevel;
+use get_my_notion_mcp::middleware::*;
+use get_my_notion_mcp::server::{self, McpServer};
+use get_my_notion_mcp::utils::*;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Transport {
+    /// Newline-delimited JSON-RPC over stdin/stdout
+    Stdio,
+    /// MCP Streamable HTTP
+    Http,
+    /// Legacy HTTP+SSE transport from protocol revision 2024-11-05
+    Sse,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum WritePolicy {
+    /// Commit to notes/<topic> branches and open pull requests for review
+    PullRequest,
+    /// Commit straight to main
+    Direct,
+}
 
 #[derive(Parser)]
 #[command(name = "get-my-notion-mcp")]
 #[command(about = "MCP server for accessing my-notion GitHub repository")]
-struct Cli {}
+struct Cli {
+    /// Transport used to talk to MCP clients
+    #[arg(long, value_enum, default_value = "stdio")]
+    transport: Transport,
+
+    /// Address to listen on for HTTP transports
+    #[arg(long, default_value = http_constants::DEFAULT_LISTEN)]
+    listen: SocketAddr,
+
+    /// Seconds a tool call or resource read may take before it fails
+    #[arg(long, default_value_t = mcp_constants::DEFAULT_TOOL_TIMEOUT_SECS)]
+    tool_timeout: u64,
+
+    /// Maximum number of concurrent calls per tool
+    #[arg(long, default_value_t = mcp_constants::DEFAULT_MAX_CONCURRENT_TOOL_CALLS, value_parser = parse_concurrency_limit)]
+    max_concurrent_calls: usize,
+
+    /// Items per page of tools/list, resources/list and list_files results
+    #[arg(long, default_value_t = mcp_constants::DEFAULT_PAGE_SIZE)]
+    page_size: usize,
+
+    /// Maximum size in bytes of each text result before it is truncated
+    #[arg(long, default_value_t = mcp_constants::DEFAULT_MAX_RESULT_BYTES)]
+    max_result_bytes: usize,
+
+    /// Minimum level of local log lines (debug, info, notice, warning, error, ...)
+    #[arg(long, default_value = "info", value_parser = parse_log_level)]
+    log_level: LoggingLevel,
+
+    /// Write logs to this file instead of stderr
+    #[arg(long)]
+    log_file: Option<PathBuf>,
+
+    /// Enable the tools that commit to the repository (needs GITHUB_TOKEN)
+    #[arg(long)]
+    allow_write: bool,
+
+    /// Where the write tools commit
+    #[arg(long, value_enum, default_value = "pull-request")]
+    write_policy: WritePolicy,
+}
+
+fn parse_log_level(level: &str) -> Result<LoggingLevel, String> {
+    LoggingLevel::parse(level).ok_or_else(|| format!("unknown log level '{}'", level))
+}
+
+// A limit of zero would leave every call waiting forever
+fn parse_concurrency_limit(limit: &str) -> Result<usize, String> {
+    match limit.parse::<usize>() {
+        Ok(0) => Err("must be at least 1".to_string()),
+        Ok(limit) => Ok(limit),
+        Err(e) => Err(e.to_string()),
+    }
+}
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let _cli = Cli::parse();
+    let cli = Cli::parse();
+
+    let log_target = cli.log_file.map_or(LogTarget::Stderr, LogTarget::File);
+    logging::init(cli.log_level, log_target)?;
+
+    let token = std::env::var(github_constants::TOKEN_ENV).ok().filter(|token| !token.is_empty());
+    if cli.allow_write && token.is_none() {
+        anyhow::bail!(errors::TOKEN_REQUIRED);
+    }
+
+    let transport = match cli.transport {
+        Transport::Stdio => server::Transport::Stdio,
+        Transport::Http => server::Transport::StreamableHttp(cli.listen),
+        Transport::Sse => server::Transport::Sse(cli.listen),
+    };
+
+    // Every handler uses a clone of one client, so writes invalidate the
+    // tree listing the read tools see. The token, when set, also raises the
+    // rate limit for reads and gives access to a private repository.
+    let github_client = match token.clone() {
+        Some(token) => GitHubClient::new_default().with_token(token),
+        None => GitHubClient::new_default(),
+    };
+
+    let mut builder = McpServer::builder()
+        .instructions(create_server_instructions(
+            github_constants::DEFAULT_OWNER,
+            github_constants::DEFAULT_REPO,
+            &[],
+        ))
+        .transport(transport)
+        .page_size(cli.page_size)
+        // Policies applied around every tool call and resource read, outermost first.
+        // Note contents never reach the logs, and waiting for a slot counts against the timeout.
+        .middleware(RedactArgumentsMiddleware::new(mcp_constants::REDACTED_ARGUMENTS.iter().copied()))
+        .middleware(LoggingMiddleware)
+        .middleware(TimeoutMiddleware::new(Duration::from_secs(cli.tool_timeout)))
+        .middleware(ConcurrencyLimitMiddleware::new(cli.max_concurrent_calls))
+        .middleware(ResultSizeLimitMiddleware::new(cli.max_result_bytes))
+        // Add tools for GitHub repository access using utility functions
+        .tool(
+            create_list_files_tool(),
+            ListFilesHandler::new_with_client(github_client.clone()).with_page_size(cli.page_size),
+        )
+        .tool(create_get_file_content_tool(), GetFileContentHandler::new_with_client(github_client.clone()))
+        .tool(create_get_latest_commit_tool(), GetLatestCommitHandler::new_with_client(github_client.clone()))
+        .tool(create_summarize_note_tool(), SummarizeNoteHandler::new_with_client(github_client.clone()))
+        .resource(create_repo_info_resource(), NotionRepoResourceHandler::new_with_client(github_client.clone()))
+        .resource_template(create_file_resource_template(), NoteFileResourceHandler::new_with_client(github_client.clone()))
+        // Add note-centric prompt templates
+        .prompt(create_summarize_note_prompt(), SummarizeNotePromptHandler::new_with_client(github_client.clone()))
+        .prompt(create_weekly_review_prompt(), WeeklyReviewPromptHandler::new_with_client(github_client.clone()))
+        .prompt(create_find_related_notes_prompt(), FindRelatedNotesPromptHandler::new_with_client(github_client.clone()));
+
+    // Write tools are opt-in and commit with the configured token
+    if cli.allow_write {
+        let write_policy = match cli.write_policy {
+            WritePolicy::PullRequest => handlers::WritePolicy::PullRequest,
+            WritePolicy::Direct => handlers::WritePolicy::Direct,
+        };
+        builder = builder
+            .tool(
+                create_create_or_update_file_tool(),
+                CreateOrUpdateFileHandler::new_with_client(github_client.clone()).with_write_policy(write_policy),
+            )
+            .tool(
+                create_delete_file_tool(),
+                DeleteFileHandler::new_with_client(github_client.clone()).with_write_policy(write_policy),
+            )
+            .tool(
+                create_commit_changes_tool(),
+                CommitChangesHandler::new_with_client(github_client.clone()).with_write_policy(write_policy),
+            )
+            .tool(create_create_branch_tool(), CreateBranchHandler::new_with_client(github_client.clone()))
+            .tool(create_open_pull_request_tool(), OpenPullRequestHandler::new_with_client(github_client.clone()));
+    }
 
-    let mut server = McpServer::new();
+    let server = builder.build();
 
-    // Add tools for GitHub repository access using utility functions
-    server.add_tool(create_list_files_tool(), ListFilesHandler::new());
-    server.add_tool(create_get_file_content_tool(), GetFileContentHandler::new());
-    server.add_tool(create_get_latest_commit_tool(), GetLatestCommitHandler::new());
-    server.add_resource(create_repo_info_resource(), NotionRepoResourceHandler::new());
+    // The layout summary is best effort and fetched in the background;
+    // clients that initialize before it arrives get the instructions without it
+    let handle = server.handle();
+    tokio::spawn(async move {
+        if let Ok(top_level) = github_client.list_files(None, None).await {
+            handle.set_instructions(create_server_instructions(
+                github_constants::DEFAULT_OWNER,
+                github_constants::DEFAULT_REPO,
+                &top_level,
+            ));
+        }
+    });
 
     server.run().await
 }
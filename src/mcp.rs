@@ -1,7 +1,8 @@
+use crate::constants::mcp as mcp_constants;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum RequestId {
     String(String),
@@ -9,11 +10,118 @@ pub enum RequestId {
     Null,
 }
 
+/// MCP protocol revisions this server can speak, ordered oldest first.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ProtocolVersion {
+    #[default]
+    V2024_11_05,
+    V2025_03_26,
+    V2025_06_18,
+}
+
+impl ProtocolVersion {
+    pub const ALL: [ProtocolVersion; 3] = [Self::V2024_11_05, Self::V2025_03_26, Self::V2025_06_18];
+    pub const LATEST: ProtocolVersion = Self::V2025_06_18;
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::V2024_11_05 => mcp_constants::PROTOCOL_VERSION_2024_11_05,
+            Self::V2025_03_26 => mcp_constants::PROTOCOL_VERSION_2025_03_26,
+            Self::V2025_06_18 => mcp_constants::PROTOCOL_VERSION_2025_06_18,
+        }
+    }
+
+    pub fn parse(version: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|supported| supported.as_str() == version)
+    }
+
+    /// Picks the newest supported revision that is not newer than the one the
+    /// client requested. Clients older than every supported revision get the
+    /// latest one and may disconnect if they cannot speak it.
+    pub fn negotiate(requested: &str) -> Self {
+        // Revisions are dates, so string order is release order
+        Self::ALL
+            .into_iter()
+            .rev()
+            .find(|supported| supported.as_str() <= requested)
+            .unwrap_or(Self::LATEST)
+    }
+
+    /// `completions` capability, tool annotations and audio content (2025-03-26)
+    pub fn supports_completions(&self) -> bool {
+        *self >= Self::V2025_03_26
+    }
+
+    pub fn supports_tool_annotations(&self) -> bool {
+        *self >= Self::V2025_03_26
+    }
+
+    /// `structuredContent` and `outputSchema` on tools (2025-06-18)
+    pub fn supports_structured_content(&self) -> bool {
+        *self >= Self::V2025_06_18
+    }
+
+    /// Human-readable `title` fields on tools and server info (2025-06-18)
+    pub fn supports_titles(&self) -> bool {
+        *self >= Self::V2025_06_18
+    }
+
+    /// JSON-RPC batches, which 2025-06-18 removed again
+    pub fn supports_batches(&self) -> bool {
+        *self < Self::V2025_06_18
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Tool {
     pub name: String,
+    /// Display name for user interfaces (2025-06-18).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
     pub description: String,
+    #[serde(rename = "inputSchema", alias = "input_schema")]
     pub input_schema: serde_json::Value,
+    /// JSON Schema of the tool's `structuredContent` (2025-06-18).
+    #[serde(rename = "outputSchema", default, skip_serializing_if = "Option::is_none")]
+    pub output_schema: Option<serde_json::Value>,
+    /// Hints about the tool's behavior (2025-03-26).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub annotations: Option<ToolAnnotations>,
+}
+
+impl Tool {
+    /// Drops the fields `protocol_version` does not define.
+    pub fn for_protocol(&self, protocol_version: ProtocolVersion) -> Self {
+        let mut tool = self.clone();
+        if !protocol_version.supports_structured_content() {
+            tool.output_schema = None;
+        }
+        if !protocol_version.supports_titles() {
+            tool.title = None;
+        }
+        if !protocol_version.supports_tool_annotations() {
+            tool.annotations = None;
+        }
+        tool
+    }
+}
+
+/// Hints that let clients decide whether a call needs the user's confirmation.
+/// They describe the tool's intent and are not guarantees.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ToolAnnotations {
+    /// The tool does not modify anything.
+    #[serde(rename = "readOnlyHint", default, skip_serializing_if = "Option::is_none")]
+    pub read_only_hint: Option<bool>,
+    /// Modifications may delete or overwrite data (meaningful only when not read-only).
+    #[serde(rename = "destructiveHint", default, skip_serializing_if = "Option::is_none")]
+    pub destructive_hint: Option<bool>,
+    /// Repeating a call with the same arguments has no further effect.
+    #[serde(rename = "idempotentHint", default, skip_serializing_if = "Option::is_none")]
+    pub idempotent_hint: Option<bool>,
+    /// The tool talks to systems outside the server, such as the GitHub API.
+    #[serde(rename = "openWorldHint", default, skip_serializing_if = "Option::is_none")]
+    pub open_world_hint: Option<bool>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -21,9 +129,78 @@ pub struct Resource {
     pub uri: String,
     pub name: String,
     pub description: Option<String>,
+    #[serde(rename = "mimeType", alias = "mime_type")]
+    pub mime_type: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResourceTemplate {
+    #[serde(rename = "uriTemplate")]
+    pub uri_template: String,
+    pub name: String,
+    pub description: Option<String>,
+    #[serde(rename = "mimeType")]
     pub mime_type: Option<String>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Prompt {
+    pub name: String,
+    pub description: Option<String>,
+    pub arguments: Option<Vec<PromptArgument>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromptArgument {
+    pub name: String,
+    pub description: Option<String>,
+    pub required: Option<bool>,
+}
+
+/// Severity of a log message, ordered from least to most severe (RFC 5424).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LoggingLevel {
+    Debug,
+    Info,
+    Notice,
+    Warning,
+    Error,
+    Critical,
+    Alert,
+    Emergency,
+}
+
+impl LoggingLevel {
+    pub const ALL: [LoggingLevel; 8] = [
+        Self::Debug,
+        Self::Info,
+        Self::Notice,
+        Self::Warning,
+        Self::Error,
+        Self::Critical,
+        Self::Alert,
+        Self::Emergency,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Debug => "debug",
+            Self::Info => "info",
+            Self::Notice => "notice",
+            Self::Warning => "warning",
+            Self::Error => "error",
+            Self::Critical => "critical",
+            Self::Alert => "alert",
+            Self::Emergency => "emergency",
+        }
+    }
+
+    pub fn parse(level: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|known| known.as_str() == level)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "method")]
 pub enum McpRequest {
@@ -32,27 +209,73 @@ pub enum McpRequest {
         id: RequestId,
         params: InitializeParams,
     },
+    #[serde(rename = "ping")]
+    Ping { id: RequestId },
     #[serde(rename = "tools/list")]
-    ListTools { id: RequestId },
+    ListTools {
+        id: RequestId,
+        #[serde(default)]
+        params: Option<PaginatedParams>,
+    },
     #[serde(rename = "tools/call")]
     CallTool {
         id: RequestId,
         params: CallToolParams,
     },
     #[serde(rename = "resources/list")]
-    ListResources { id: RequestId },
+    ListResources {
+        id: RequestId,
+        #[serde(default)]
+        params: Option<PaginatedParams>,
+    },
     #[serde(rename = "resources/read")]
     ReadResource {
         id: RequestId,
         params: ReadResourceParams,
     },
+    #[serde(rename = "resources/templates/list")]
+    ListResourceTemplates { id: RequestId },
+    #[serde(rename = "prompts/list")]
+    ListPrompts { id: RequestId },
+    #[serde(rename = "prompts/get")]
+    GetPrompt {
+        id: RequestId,
+        params: GetPromptParams,
+    },
+    #[serde(rename = "completion/complete")]
+    Complete {
+        id: RequestId,
+        params: CompleteParams,
+    },
+    #[serde(rename = "logging/setLevel")]
+    SetLevel {
+        id: RequestId,
+        params: SetLevelParams,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetLevelParams {
+    /// Minimum severity of the `notifications/message` the client wants to receive.
+    pub level: LoggingLevel,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "method")]
 pub enum McpNotification {
-    #[serde(rename = "initialized")]
+    #[serde(rename = "notifications/initialized", alias = "initialized")]
     Initialized,
+    #[serde(rename = "notifications/cancelled")]
+    Cancelled { params: CancelledParams },
+    #[serde(rename = "notifications/roots/list_changed")]
+    RootsListChanged,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CancelledParams {
+    #[serde(rename = "requestId")]
+    pub request_id: RequestId,
+    pub reason: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -68,16 +291,21 @@ pub struct InitializeParams {
 pub struct ClientCapabilities {
     pub roots: Option<RootsCapability>,
     pub sampling: Option<SamplingCapability>,
+    pub elicitation: Option<ElicitationCapability>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RootsCapability {
+    #[serde(rename = "listChanged", alias = "list_changed", default)]
     pub list_changed: Option<bool>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SamplingCapability {}
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ElicitationCapability {}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ClientInfo {
     pub name: String,
@@ -88,6 +316,21 @@ pub struct ClientInfo {
 pub struct CallToolParams {
     pub name: String,
     pub arguments: Option<HashMap<String, serde_json::Value>>,
+    #[serde(rename = "_meta", default, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<RequestMeta>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RequestMeta {
+    #[serde(rename = "progressToken")]
+    pub progress_token: Option<ProgressToken>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ProgressToken {
+    String(String),
+    Number(i64),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -95,6 +338,38 @@ pub struct ReadResourceParams {
     pub uri: String,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetPromptParams {
+    pub name: String,
+    pub arguments: Option<HashMap<String, String>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompleteParams {
+    #[serde(rename = "ref")]
+    pub reference: CompletionReference,
+    pub argument: CompletionArgument,
+}
+
+/// What is being completed. `ref/tool` is an extension of this server that
+/// completes tool arguments the same way prompt arguments are completed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum CompletionReference {
+    #[serde(rename = "ref/prompt")]
+    Prompt { name: String },
+    #[serde(rename = "ref/resource")]
+    Resource { uri: String },
+    #[serde(rename = "ref/tool")]
+    Tool { name: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompletionArgument {
+    pub name: String,
+    pub value: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum McpResponse {
@@ -123,11 +398,179 @@ pub enum McpResponse {
         id: RequestId,
         result: ReadResourceResult,
     },
+    ListResourceTemplates {
+        jsonrpc: String,
+        id: RequestId,
+        result: ListResourceTemplatesResult,
+    },
+    ListPrompts {
+        jsonrpc: String,
+        id: RequestId,
+        result: ListPromptsResult,
+    },
+    GetPrompt {
+        jsonrpc: String,
+        id: RequestId,
+        result: GetPromptResult,
+    },
+    Complete {
+        jsonrpc: String,
+        id: RequestId,
+        result: CompleteResult,
+    },
     Error {
         jsonrpc: String,
         id: RequestId,
         error: McpError,
     },
+    Empty {
+        jsonrpc: String,
+        id: RequestId,
+        result: EmptyResult,
+    },
+    /// Responses to the requests of a JSON-RPC batch, in completion order.
+    Batch(Vec<McpResponse>),
+}
+
+/// Requests the server sends to the client. Responses are matched to them by ID.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "method")]
+pub enum ServerRequest {
+    #[serde(rename = "roots/list")]
+    ListRoots,
+    #[serde(rename = "sampling/createMessage")]
+    CreateMessage { params: CreateMessageParams },
+    #[serde(rename = "elicitation/create")]
+    Elicit { params: ElicitParams },
+}
+
+/// A `ServerRequest` framed as a JSON-RPC message.
+#[derive(Debug, Clone, Serialize)]
+pub struct OutgoingRequest {
+    pub jsonrpc: String,
+    pub id: RequestId,
+    #[serde(flatten)]
+    pub request: ServerRequest,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListRootsResult {
+    pub roots: Vec<Root>,
+}
+
+/// A directory the client exposes to the server, usually an open workspace.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Root {
+    /// A `file://` URI.
+    pub uri: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+/// Asks the client's model for a completion. The client may show the request
+/// to the user and choose the model itself.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateMessageParams {
+    pub messages: Vec<SamplingMessage>,
+    #[serde(rename = "systemPrompt", default, skip_serializing_if = "Option::is_none")]
+    pub system_prompt: Option<String>,
+    #[serde(rename = "maxTokens")]
+    pub max_tokens: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SamplingMessage {
+    pub role: Role,
+    pub content: SamplingContent,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum SamplingContent {
+    #[serde(rename = "text")]
+    Text { text: String },
+    /// Image or audio content, which the server does not use.
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateMessageResult {
+    pub role: Role,
+    pub content: SamplingContent,
+    /// The model that produced the message.
+    pub model: String,
+    #[serde(rename = "stopReason", default, skip_serializing_if = "Option::is_none")]
+    pub stop_reason: Option<String>,
+}
+
+/// Asks the user for input through the client. `requested_schema` is a flat
+/// object schema with primitive properties.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ElicitParams {
+    pub message: String,
+    #[serde(rename = "requestedSchema")]
+    pub requested_schema: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ElicitResult {
+    pub action: ElicitAction,
+    /// The submitted values; only present when the user accepted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content: Option<HashMap<String, serde_json::Value>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ElicitAction {
+    Accept,
+    Decline,
+    Cancel,
+}
+
+/// Notifications sent from the server to the client.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "method")]
+pub enum ServerNotification {
+    #[serde(rename = "notifications/progress")]
+    Progress {
+        jsonrpc: String,
+        params: ProgressParams,
+    },
+    #[serde(rename = "notifications/tools/list_changed")]
+    ToolListChanged {
+        jsonrpc: String,
+    },
+    #[serde(rename = "notifications/resources/list_changed")]
+    ResourceListChanged {
+        jsonrpc: String,
+    },
+    #[serde(rename = "notifications/message")]
+    LogMessage {
+        jsonrpc: String,
+        params: LoggingMessageParams,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoggingMessageParams {
+    pub level: LoggingLevel,
+    /// Component that emitted the message, e.g. `github`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logger: Option<String>,
+    pub data: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProgressParams {
+    #[serde(rename = "progressToken")]
+    pub progress_token: ProgressToken,
+    pub progress: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -137,12 +580,20 @@ pub struct InitializeResult {
     pub capabilities: ServerCapabilities,
     #[serde(rename = "serverInfo")]
     pub server_info: ServerInfo,
+    /// How to use the server, shown to the model by clients that support it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instructions: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ServerCapabilities {
     pub tools: Option<ToolsCapability>,
     pub resources: Option<ResourcesCapability>,
+    pub prompts: Option<PromptsCapability>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub completions: Option<CompletionsCapability>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub logging: Option<LoggingCapability>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -158,20 +609,77 @@ pub struct ResourcesCapability {
     pub list_changed: Option<bool>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromptsCapability {
+    #[serde(rename = "listChanged")]
+    pub list_changed: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompletionsCapability {}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoggingCapability {}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ServerInfo {
     pub name: String,
     pub version: String,
+    /// Display name for user interfaces (2025-06-18).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// Where users can learn more about the server (2025-06-18).
+    #[serde(rename = "websiteUrl", default, skip_serializing_if = "Option::is_none")]
+    pub website_url: Option<String>,
+}
+
+impl ServerInfo {
+    /// Drops the fields `protocol_version` does not define.
+    pub fn for_protocol(&self, protocol_version: ProtocolVersion) -> Self {
+        let mut server_info = self.clone();
+        if !protocol_version.supports_titles() {
+            server_info.title = None;
+            server_info.website_url = None;
+        }
+        server_info
+    }
+}
+
+/// Params of list requests that support pagination.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PaginatedParams {
+    /// Opaque cursor from a previous result's `nextCursor`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ListToolsResult {
     pub tools: Vec<Tool>,
+    /// Cursor for the next page; absent on the last page.
+    #[serde(rename = "nextCursor", default, skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CallToolResult {
     pub content: Vec<ToolContent>,
+    /// Typed result matching the tool's `outputSchema` (2025-06-18).
+    #[serde(rename = "structuredContent", default, skip_serializing_if = "Option::is_none")]
+    pub structured_content: Option<serde_json::Value>,
+    /// Set when the tool ran but failed; the content describes the failure.
+    #[serde(rename = "isError", default, skip_serializing_if = "Option::is_none")]
+    pub is_error: Option<bool>,
+}
+
+impl CallToolResult {
+    /// Drops the fields `protocol_version` does not define.
+    pub fn for_protocol(mut self, protocol_version: ProtocolVersion) -> Self {
+        if !protocol_version.supports_structured_content() {
+            self.structured_content = None;
+        }
+        self
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -184,6 +692,9 @@ pub enum ToolContent {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ListResourcesResult {
     pub resources: Vec<Resource>,
+    /// Cursor for the next page; absent on the last page.
+    #[serde(rename = "nextCursor", default, skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -203,6 +714,60 @@ pub enum ResourceContent {
     },
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListResourceTemplatesResult {
+    #[serde(rename = "resourceTemplates")]
+    pub resource_templates: Vec<ResourceTemplate>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListPromptsResult {
+    pub prompts: Vec<Prompt>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetPromptResult {
+    pub description: Option<String>,
+    pub messages: Vec<PromptMessage>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromptMessage {
+    pub role: Role,
+    pub content: PromptContent,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Role {
+    #[serde(rename = "user")]
+    User,
+    #[serde(rename = "assistant")]
+    Assistant,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum PromptContent {
+    #[serde(rename = "text")]
+    Text { text: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmptyResult {}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompleteResult {
+    pub completion: Completion,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Completion {
+    pub values: Vec<String>,
+    pub total: Option<u32>,
+    #[serde(rename = "hasMore")]
+    pub has_more: Option<bool>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct McpError {
     pub code: i32,
@@ -0,0 +1,500 @@
+use crate::constants::{errors, mcp as mcp_constants};
+use crate::error::ToolError;
+use crate::logging;
+use crate::mcp::*;
+use crate::server::{ResourceHandler, ToolContext, ToolHandler};
+use anyhow::{anyhow, Result};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+
+/// Wraps tool calls and resource reads. Each method receives the rest of the
+/// chain as `next` and decides whether, when and with what to continue.
+/// Middleware added first runs outermost.
+#[async_trait::async_trait]
+pub trait Middleware: Send + Sync {
+    async fn call_tool<'a>(&self, call: ToolCall<'a>, next: ToolNext<'a>) -> Result<CallToolResult> {
+        next.run(call).await
+    }
+
+    async fn read_resource<'a>(&self, uri: &'a str, next: ResourceNext<'a>) -> Result<ReadResourceResult> {
+        next.run(uri).await
+    }
+}
+
+/// A tool call on its way through the middleware chain.
+pub struct ToolCall<'a> {
+    pub name: &'a str,
+    pub arguments: Option<HashMap<String, serde_json::Value>>,
+    pub context: &'a ToolContext,
+    /// Names of arguments whose values must not be logged.
+    pub redacted: HashSet<String>,
+}
+
+impl<'a> ToolCall<'a> {
+    pub fn new(name: &'a str, arguments: Option<HashMap<String, serde_json::Value>>, context: &'a ToolContext) -> Self {
+        Self {
+            name,
+            arguments,
+            context,
+            redacted: HashSet::new(),
+        }
+    }
+
+    /// The arguments with every redacted value replaced by a placeholder,
+    /// including values nested in objects and arrays (e.g. each change's `content`).
+    pub fn loggable_arguments(&self) -> serde_json::Value {
+        let arguments = self
+            .arguments
+            .iter()
+            .flatten()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+
+        self.redact(serde_json::Value::Object(arguments))
+    }
+
+    fn redact(&self, value: serde_json::Value) -> serde_json::Value {
+        match value {
+            serde_json::Value::Object(fields) => fields
+                .into_iter()
+                .map(|(name, value)| {
+                    let value = if self.redacted.contains(&name) {
+                        serde_json::Value::String(mcp_constants::REDACTED.to_string())
+                    } else {
+                        self.redact(value)
+                    };
+                    (name, value)
+                })
+                .collect(),
+            serde_json::Value::Array(items) => items.into_iter().map(|item| self.redact(item)).collect(),
+            value => value,
+        }
+    }
+}
+
+/// The remainder of the chain, ending in the tool handler.
+pub struct ToolNext<'a> {
+    middleware: &'a [Arc<dyn Middleware>],
+    handler: &'a dyn ToolHandler,
+}
+
+impl<'a> ToolNext<'a> {
+    pub(crate) fn new(middleware: &'a [Arc<dyn Middleware>], handler: &'a dyn ToolHandler) -> Self {
+        Self { middleware, handler }
+    }
+
+    pub async fn run(self, call: ToolCall<'a>) -> Result<CallToolResult> {
+        match self.middleware.split_first() {
+            Some((first, rest)) => first.call_tool(call, ToolNext::new(rest, self.handler)).await,
+            None => self.handler.call(call.arguments, call.context).await,
+        }
+    }
+}
+
+/// The remainder of the chain, ending in the resource handler.
+pub struct ResourceNext<'a> {
+    middleware: &'a [Arc<dyn Middleware>],
+    handler: &'a dyn ResourceHandler,
+}
+
+impl<'a> ResourceNext<'a> {
+    pub(crate) fn new(middleware: &'a [Arc<dyn Middleware>], handler: &'a dyn ResourceHandler) -> Self {
+        Self { middleware, handler }
+    }
+
+    pub async fn run(self, uri: &'a str) -> Result<ReadResourceResult> {
+        match self.middleware.split_first() {
+            Some((first, rest)) => first.read_resource(uri, ResourceNext::new(rest, self.handler)).await,
+            None => self.handler.read(uri).await,
+        }
+    }
+}
+
+/// Logs every call and read with its duration and outcome through `crate::logging`.
+pub struct LoggingMiddleware;
+
+#[async_trait::async_trait]
+impl Middleware for LoggingMiddleware {
+    async fn call_tool<'a>(&self, call: ToolCall<'a>, next: ToolNext<'a>) -> Result<CallToolResult> {
+        let name = call.name;
+        let arguments = call.loggable_arguments();
+        let started = Instant::now();
+
+        let result = next.run(call).await;
+        let outcome = match &result {
+            Ok(result) if result.is_error == Some(true) => "error result".to_string(),
+            Ok(_) => "ok".to_string(),
+            Err(e) => format!("failed: {}", e),
+        };
+        logging::log(
+            LoggingLevel::Info,
+            mcp_constants::LOGGER_TOOLS,
+            serde_json::json!({
+                "method": "tools/call",
+                "tool": name,
+                "arguments": arguments,
+                "outcome": outcome,
+                "elapsedMs": started.elapsed().as_millis() as u64,
+            }),
+        );
+
+        result
+    }
+
+    async fn read_resource<'a>(&self, uri: &'a str, next: ResourceNext<'a>) -> Result<ReadResourceResult> {
+        let started = Instant::now();
+
+        let result = next.run(uri).await;
+        let outcome = match &result {
+            Ok(_) => "ok".to_string(),
+            Err(e) => format!("failed: {}", e),
+        };
+        logging::log(
+            LoggingLevel::Info,
+            mcp_constants::LOGGER_TOOLS,
+            serde_json::json!({
+                "method": "resources/read",
+                "uri": uri,
+                "outcome": outcome,
+                "elapsedMs": started.elapsed().as_millis() as u64,
+            }),
+        );
+
+        result
+    }
+}
+
+/// Keeps the values of the named arguments out of logs written by later middleware.
+pub struct RedactArgumentsMiddleware {
+    names: HashSet<String>,
+}
+
+impl RedactArgumentsMiddleware {
+    pub fn new<I, S>(names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            names: names.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Middleware for RedactArgumentsMiddleware {
+    async fn call_tool<'a>(&self, mut call: ToolCall<'a>, next: ToolNext<'a>) -> Result<CallToolResult> {
+        call.redacted.extend(self.names.iter().cloned());
+        next.run(call).await
+    }
+}
+
+/// Fails calls and reads that take longer than their time limit.
+pub struct TimeoutMiddleware {
+    default: Duration,
+    per_tool: HashMap<String, Duration>,
+}
+
+impl TimeoutMiddleware {
+    pub fn new(default: Duration) -> Self {
+        Self {
+            default,
+            per_tool: HashMap::new(),
+        }
+    }
+
+    /// Overrides the limit for one tool.
+    pub fn with_tool_timeout(mut self, name: &str, timeout: Duration) -> Self {
+        self.per_tool.insert(name.to_string(), timeout);
+        self
+    }
+}
+
+#[async_trait::async_trait]
+impl Middleware for TimeoutMiddleware {
+    async fn call_tool<'a>(&self, call: ToolCall<'a>, next: ToolNext<'a>) -> Result<CallToolResult> {
+        let timeout = self.per_tool.get(call.name).copied().unwrap_or(self.default);
+
+        tokio::time::timeout(timeout, next.run(call))
+            .await
+            .unwrap_or_else(|_| Err(anyhow!("{} after {:?}", errors::TIMED_OUT, timeout)))
+    }
+
+    async fn read_resource<'a>(&self, uri: &'a str, next: ResourceNext<'a>) -> Result<ReadResourceResult> {
+        tokio::time::timeout(self.default, next.run(uri))
+            .await
+            .unwrap_or_else(|_| Err(anyhow!("{} after {:?}", errors::TIMED_OUT, self.default)))
+    }
+}
+
+/// Limits how many calls of the same tool, and how many resource reads, run at once.
+/// Further calls wait for a slot.
+pub struct ConcurrencyLimitMiddleware {
+    limit: usize,
+    semaphores: Mutex<HashMap<String, Arc<Semaphore>>>,
+}
+
+impl ConcurrencyLimitMiddleware {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            semaphores: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn semaphore(&self, key: &str) -> Arc<Semaphore> {
+        let mut semaphores = self.semaphores.lock().unwrap();
+        let semaphore = semaphores
+            .entry(key.to_string())
+            .or_insert_with(|| Arc::new(Semaphore::new(self.limit)));
+        Arc::clone(semaphore)
+    }
+}
+
+#[async_trait::async_trait]
+impl Middleware for ConcurrencyLimitMiddleware {
+    async fn call_tool<'a>(&self, call: ToolCall<'a>, next: ToolNext<'a>) -> Result<CallToolResult> {
+        let _permit = self.semaphore(call.name).acquire_owned().await?;
+        next.run(call).await
+    }
+
+    async fn read_resource<'a>(&self, uri: &'a str, next: ResourceNext<'a>) -> Result<ReadResourceResult> {
+        // Resource reads share a single pool of slots
+        let _permit = self.semaphore("resources/read").acquire_owned().await?;
+        next.run(uri).await
+    }
+}
+
+/// Caps the size of results: text is cut at `max_bytes` with a note, while
+/// oversized structured content and binary resources are refused, since
+/// cutting them would break their schema or encoding. Tool results are
+/// adapted to the caller's protocol first, so structured content is only
+/// checked for clients that receive it.
+pub struct ResultSizeLimitMiddleware {
+    max_bytes: usize,
+}
+
+impl ResultSizeLimitMiddleware {
+    pub fn new(max_bytes: usize) -> Self {
+        Self { max_bytes }
+    }
+
+    fn truncate(&self, text: &mut String) {
+        if text.len() <= self.max_bytes {
+            return;
+        }
+
+        let mut end = self.max_bytes;
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        let removed = text.len() - end;
+        text.truncate(end);
+        text.push_str(&format!("\n\n[{}: {} bytes omitted]", errors::RESULT_TRUNCATED, removed));
+    }
+}
+
+#[async_trait::async_trait]
+impl Middleware for ResultSizeLimitMiddleware {
+    async fn call_tool<'a>(&self, call: ToolCall<'a>, next: ToolNext<'a>) -> Result<CallToolResult> {
+        let protocol_version = call.context.protocol_version();
+        let mut result = next.run(call).await?.for_protocol(protocol_version);
+
+        let structured_size = result
+            .structured_content
+            .as_ref()
+            .map_or(0, |structured| structured.to_string().len());
+        if structured_size > self.max_bytes {
+            return Ok(CallToolResult {
+                content: vec![ToolContent::Text {
+                    text: format!("{}: {} bytes (limit {})", errors::RESULT_TOO_LARGE, structured_size, self.max_bytes),
+                }],
+                structured_content: None,
+                is_error: Some(true),
+            });
+        }
+        for content in &mut result.content {
+            let ToolContent::Text { text } = content;
+            self.truncate(text);
+        }
+
+        Ok(result)
+    }
+
+    async fn read_resource<'a>(&self, uri: &'a str, next: ResourceNext<'a>) -> Result<ReadResourceResult> {
+        let mut result = next.run(uri).await?;
+
+        for content in &mut result.contents {
+            match content {
+                ResourceContent::Text { text, .. } => self.truncate(text),
+                ResourceContent::Blob { blob, .. } if blob.len() > self.max_bytes => {
+                    return Err(ToolError::TooLarge {
+                        path: uri.to_string(),
+                        size: blob.len() as u64,
+                    }
+                    .into());
+                }
+                ResourceContent::Blob { .. } => {}
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Echoes its arguments back after an optional delay
+    struct EchoToolHandler;
+
+    #[async_trait::async_trait]
+    impl ToolHandler for EchoToolHandler {
+        async fn call(
+            &self,
+            arguments: Option<HashMap<String, serde_json::Value>>,
+            _context: &ToolContext,
+        ) -> Result<CallToolResult> {
+            let arguments = arguments.unwrap_or_default();
+            if let Some(delay) = arguments.get("delay_ms").and_then(|v| v.as_u64()) {
+                tokio::time::sleep(Duration::from_millis(delay)).await;
+            }
+            let text = arguments.get("text").and_then(|v| v.as_str()).unwrap_or_default();
+            let structured = arguments.get("structured").and_then(|v| v.as_bool()).unwrap_or(true);
+            Ok(CallToolResult {
+                content: vec![ToolContent::Text { text: text.to_string() }],
+                structured_content: structured.then(|| serde_json::json!({ "text": text })),
+                is_error: None,
+            })
+        }
+    }
+
+    // Records the loggable arguments it sees
+    struct RecordingMiddleware(Mutex<Option<serde_json::Value>>);
+
+    #[async_trait::async_trait]
+    impl Middleware for RecordingMiddleware {
+        async fn call_tool<'a>(&self, call: ToolCall<'a>, next: ToolNext<'a>) -> Result<CallToolResult> {
+            *self.0.lock().unwrap() = Some(call.loggable_arguments());
+            next.run(call).await
+        }
+    }
+
+    fn arguments(value: serde_json::Value) -> Option<HashMap<String, serde_json::Value>> {
+        serde_json::from_value(value).unwrap()
+    }
+
+    async fn call(middleware: &[Arc<dyn Middleware>], value: serde_json::Value) -> Result<CallToolResult> {
+        call_with_protocol(middleware, value, ProtocolVersion::LATEST).await
+    }
+
+    async fn call_with_protocol(
+        middleware: &[Arc<dyn Middleware>],
+        value: serde_json::Value,
+        protocol_version: ProtocolVersion,
+    ) -> Result<CallToolResult> {
+        let context = ToolContext::new(None, None, protocol_version);
+        let call = ToolCall::new("echo", arguments(value), &context);
+        ToolNext::new(middleware, &EchoToolHandler).run(call).await
+    }
+
+    #[tokio::test]
+    async fn test_redaction_applies_to_later_middleware_only() {
+        let recorder = Arc::new(RecordingMiddleware(Mutex::new(None)));
+        let middleware: Vec<Arc<dyn Middleware>> = vec![
+            Arc::new(RedactArgumentsMiddleware::new(["token"])),
+            recorder.clone(),
+        ];
+
+        let result = call(&middleware, serde_json::json!({ "text": "hi", "token": "secret" })).await.unwrap();
+
+        let ToolContent::Text { text } = &result.content[0];
+        assert_eq!(text, "hi");
+        let logged = recorder.0.lock().unwrap().clone().unwrap();
+        assert_eq!(logged["token"], mcp_constants::REDACTED);
+        assert_eq!(logged["text"], "hi");
+
+        call(&middleware, serde_json::json!({ "changes": [{ "path": "a.md", "token": "secret" }] })).await.unwrap();
+        let logged = recorder.0.lock().unwrap().clone().unwrap();
+        assert_eq!(logged["changes"][0]["token"], mcp_constants::REDACTED);
+        assert_eq!(logged["changes"][0]["path"], "a.md");
+    }
+
+    #[tokio::test]
+    async fn test_timeout_uses_per_tool_limit() {
+        let middleware: Vec<Arc<dyn Middleware>> = vec![Arc::new(
+            TimeoutMiddleware::new(Duration::from_secs(10)).with_tool_timeout("echo", Duration::from_millis(20)),
+        )];
+
+        let error = call(&middleware, serde_json::json!({ "delay_ms": 200 })).await.unwrap_err();
+        assert!(error.to_string().starts_with(errors::TIMED_OUT));
+
+        assert!(call(&middleware, serde_json::json!({ "delay_ms": 1 })).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_result_size_limit_truncates_text() {
+        let middleware: Vec<Arc<dyn Middleware>> = vec![Arc::new(ResultSizeLimitMiddleware::new(8))];
+
+        let result = call(&middleware, serde_json::json!({ "text": "héllo wörld", "structured": false }))
+            .await
+            .unwrap();
+
+        let ToolContent::Text { text } = &result.content[0];
+        assert!(text.starts_with("héllo w"));
+        assert!(text.contains(errors::RESULT_TRUNCATED));
+        assert!(result.is_error.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_result_size_limit_refuses_oversized_structured_content() {
+        let middleware: Vec<Arc<dyn Middleware>> = vec![Arc::new(ResultSizeLimitMiddleware::new(8))];
+
+        let result = call(&middleware, serde_json::json!({ "text": "héllo wörld" })).await.unwrap();
+
+        let ToolContent::Text { text } = &result.content[0];
+        assert!(text.starts_with(errors::RESULT_TOO_LARGE));
+        assert_eq!(result.is_error, Some(true));
+        assert!(result.structured_content.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_result_size_limit_truncates_text_for_clients_without_structured_content() {
+        let middleware: Vec<Arc<dyn Middleware>> = vec![Arc::new(ResultSizeLimitMiddleware::new(8))];
+
+        let result = call_with_protocol(
+            &middleware,
+            serde_json::json!({ "text": "héllo wörld" }),
+            ProtocolVersion::V2025_03_26,
+        )
+        .await
+        .unwrap();
+
+        let ToolContent::Text { text } = &result.content[0];
+        assert!(text.starts_with("héllo w"));
+        assert!(text.contains(errors::RESULT_TRUNCATED));
+        assert!(result.is_error.is_none());
+        assert!(result.structured_content.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_concurrency_limit_queues_calls() {
+        let middleware: Arc<Vec<Arc<dyn Middleware>>> = Arc::new(vec![Arc::new(ConcurrencyLimitMiddleware::new(1))]);
+        let started = Instant::now();
+
+        let first = tokio::spawn({
+            let middleware = Arc::clone(&middleware);
+            async move { call(&middleware, serde_json::json!({ "delay_ms": 100 })).await.is_ok() }
+        });
+        let second = tokio::spawn({
+            let middleware = Arc::clone(&middleware);
+            async move { call(&middleware, serde_json::json!({ "delay_ms": 100 })).await.is_ok() }
+        });
+
+        assert!(first.await.unwrap() && second.await.unwrap());
+        assert!(started.elapsed() >= Duration::from_millis(200));
+    }
+}
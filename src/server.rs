@@ -1,180 +1,1838 @@
-use crate::constants::{mcp as mcp_constants, errors};
+use crate::constants::{github as github_constants, mcp as mcp_constants, errors};
+use crate::error::ToolError;
+use crate::local::LocalRepository;
+use crate::logging;
 use crate::mcp::*;
+use crate::middleware::{Middleware, ResourceNext, ToolCall, ToolNext};
 use crate::utils;
 use anyhow::Result;
+use futures_util::future::join_all;
+use serde::de::DeserializeOwned;
 use std::collections::HashMap;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, BufWriter};
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader, BufWriter};
+use tokio::sync::mpsc::WeakUnboundedSender;
+use tokio::sync::{mpsc, oneshot, Semaphore};
+use tokio::task::AbortHandle;
+
+/// A parsed incoming JSON-RPC message.
+pub(crate) enum IncomingMessage {
+    Request { id: RequestId, request: McpRequest },
+    Notification(McpNotification),
+    Invalid(McpResponse),
+    /// The client's answer to a request this server sent.
+    Response { id: RequestId, result: ClientResponse },
+    Ignored,
+    /// Members of a JSON-RPC batch; never nested.
+    Batch(Vec<IncomingMessage>),
+}
+
+/// How `McpServer::run` talks to clients.
+#[derive(Debug, Clone, Copy, Default)]
+pub enum Transport {
+    /// Newline-delimited JSON-RPC over stdin/stdout
+    #[default]
+    Stdio,
+    /// MCP Streamable HTTP on the given address
+    StreamableHttp(SocketAddr),
+    /// Legacy HTTP+SSE transport (2024-11-05) on the given address
+    Sse(SocketAddr),
+}
 
 pub struct McpServer {
+    server_info: ServerInfo,
+    capabilities: ServerCapabilities,
+    transport: Transport,
+    page_size: usize,
+    handle: ServerHandle,
+    resource_templates: Vec<ResourceTemplate>,
+    resource_template_handlers: HashMap<String, Arc<dyn ResourceHandler + Send + Sync>>,
+    prompts: Vec<Prompt>,
+    prompt_handlers: HashMap<String, Box<dyn PromptHandler + Send + Sync>>,
+    middleware: Vec<Arc<dyn Middleware>>,
+    // Session used by `handle_message`, which has no connection of its own
+    session: SessionState,
+}
+
+/// Handle for sending messages to the client of one connection.
+#[derive(Clone)]
+pub struct Peer {
+    sender: mpsc::UnboundedSender<String>,
+}
+
+impl Peer {
+    pub(crate) fn new(sender: mpsc::UnboundedSender<String>) -> Self {
+        Self { sender }
+    }
+
+    pub(crate) fn send_response(&self, response: &McpResponse) {
+        send_message(&self.sender, response);
+    }
+
+    // Passes on an already serialized message
+    pub(crate) fn forward(&self, message: String) {
+        let _ = self.sender.send(message);
+    }
+
+    pub fn send_notification(&self, notification: &ServerNotification) {
+        send_message(&self.sender, notification);
+    }
+
+    pub(crate) fn send_request(&self, request: &OutgoingRequest) {
+        send_message(&self.sender, request);
+    }
+
+    // A reference that does not keep the connection's writer alive
+    pub(crate) fn downgrade(&self) -> WeakUnboundedSender<String> {
+        self.sender.downgrade()
+    }
+}
+
+/// Tools and resources that may change while the server is running.
+#[derive(Default)]
+struct Registry {
     tools: Vec<Tool>,
+    tool_handlers: HashMap<String, Arc<dyn ToolHandler + Send + Sync>>,
     resources: Vec<Resource>,
-    tool_handlers: HashMap<String, Box<dyn ToolHandler + Send + Sync>>,
-    resource_handlers: HashMap<String, Box<dyn ResourceHandler + Send + Sync>>,
+    resource_handlers: HashMap<String, Arc<dyn ResourceHandler + Send + Sync>>,
+    instructions: Option<String>,
+}
+
+/// Handle for adding and removing tools and resources at runtime, obtained
+/// with `McpServer::handle` before the server is started. Every change is
+/// announced to connected clients with a `list_changed` notification.
+#[derive(Clone, Default)]
+pub struct ServerHandle {
+    registry: Arc<RwLock<Registry>>,
+    // Connections to notify; a connection drops out once its last sender is gone
+    peers: Arc<Mutex<Vec<WeakUnboundedSender<String>>>>,
+}
+
+impl ServerHandle {
+    /// Registers a tool, replacing any tool with the same name.
+    pub fn add_tool<H>(&self, tool: Tool, handler: H)
+    where
+        H: ToolHandler + Send + Sync + 'static,
+    {
+        {
+            let mut registry = self.registry.write().unwrap();
+            registry.tools.retain(|existing| existing.name != tool.name);
+            registry.tool_handlers.insert(tool.name.clone(), Arc::new(handler));
+            registry.tools.push(tool);
+        }
+        self.notify(ServerNotification::ToolListChanged {
+            jsonrpc: "2.0".to_string(),
+        });
+    }
+
+    /// Unregisters the tool called `name`. Returns whether it was registered.
+    pub fn remove_tool(&self, name: &str) -> bool {
+        let removed = {
+            let mut registry = self.registry.write().unwrap();
+            registry.tools.retain(|tool| tool.name != name);
+            registry.tool_handlers.remove(name).is_some()
+        };
+        if removed {
+            self.notify(ServerNotification::ToolListChanged {
+                jsonrpc: "2.0".to_string(),
+            });
+        }
+        removed
+    }
+
+    /// Registers a resource, replacing any resource with the same URI.
+    pub fn add_resource<H>(&self, resource: Resource, handler: H)
+    where
+        H: ResourceHandler + Send + Sync + 'static,
+    {
+        {
+            let mut registry = self.registry.write().unwrap();
+            registry.resources.retain(|existing| existing.uri != resource.uri);
+            registry.resource_handlers.insert(resource.uri.clone(), Arc::new(handler));
+            registry.resources.push(resource);
+        }
+        self.notify(ServerNotification::ResourceListChanged {
+            jsonrpc: "2.0".to_string(),
+        });
+    }
+
+    /// Unregisters the resource at `uri`. Returns whether it was registered.
+    pub fn remove_resource(&self, uri: &str) -> bool {
+        let removed = {
+            let mut registry = self.registry.write().unwrap();
+            registry.resources.retain(|resource| resource.uri != uri);
+            registry.resource_handlers.remove(uri).is_some()
+        };
+        if removed {
+            self.notify(ServerNotification::ResourceListChanged {
+                jsonrpc: "2.0".to_string(),
+            });
+        }
+        removed
+    }
+
+    /// Replaces the instructions sent to clients that initialize from now on.
+    pub fn set_instructions(&self, instructions: impl Into<String>) {
+        self.registry.write().unwrap().instructions = Some(instructions.into());
+    }
+
+    /// Names of the currently registered tools.
+    pub fn tool_names(&self) -> Vec<String> {
+        let registry = self.registry.read().unwrap();
+        registry.tools.iter().map(|tool| tool.name.clone()).collect()
+    }
+
+    /// URIs of the currently registered resources.
+    pub fn resource_uris(&self) -> Vec<String> {
+        let registry = self.registry.read().unwrap();
+        registry.resources.iter().map(|resource| resource.uri.clone()).collect()
+    }
+
+    // Registers a connection for list_changed notifications
+    pub(crate) fn subscribe(&self, peer: &Peer) {
+        self.peers.lock().unwrap().push(peer.downgrade());
+    }
+
+    fn notify(&self, notification: ServerNotification) {
+        self.peers.lock().unwrap().retain(|peer| match peer.upgrade() {
+            Some(sender) => {
+                send_message(&sender, &notification);
+                true
+            }
+            None => false,
+        });
+    }
+
+    fn instructions(&self) -> Option<String> {
+        self.registry.read().unwrap().instructions.clone()
+    }
+
+    fn tools(&self) -> Vec<Tool> {
+        self.registry.read().unwrap().tools.clone()
+    }
+
+    fn resources(&self) -> Vec<Resource> {
+        self.registry.read().unwrap().resources.clone()
+    }
+
+    fn tool_handler(&self, name: &str) -> Option<Arc<dyn ToolHandler + Send + Sync>> {
+        self.registry.read().unwrap().tool_handlers.get(name).cloned()
+    }
+
+    fn resource_handler(&self, uri: &str) -> Option<Arc<dyn ResourceHandler + Send + Sync>> {
+        self.registry.read().unwrap().resource_handlers.get(uri).cloned()
+    }
+}
+
+/// Requests being handled on one connection. Spawned tasks share a bounded
+/// number of permits and can be aborted by request ID.
+#[derive(Clone)]
+pub(crate) struct InFlightRequests {
+    // Each task is tagged with the spawn it came from, since clients may
+    // reuse the ID of a finished or cancelled request
+    tasks: Arc<Mutex<HashMap<RequestId, (u64, AbortHandle)>>>,
+    next_spawn: Arc<AtomicU64>,
+    permits: Arc<Semaphore>,
+}
+
+impl InFlightRequests {
+    pub(crate) fn new() -> Self {
+        Self {
+            tasks: Arc::new(Mutex::new(HashMap::new())),
+            next_spawn: Arc::new(AtomicU64::new(0)),
+            permits: Arc::new(Semaphore::new(mcp_constants::MAX_CONCURRENT_REQUESTS)),
+        }
+    }
+
+    pub(crate) fn spawn<F>(&self, id: RequestId, future: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let tasks = Arc::clone(&self.tasks);
+        let permits = Arc::clone(&self.permits);
+        let task_id = id.clone();
+        let spawn = self.next_spawn.fetch_add(1, Ordering::Relaxed);
+
+        // Hold the lock until the handle is registered so a fast task
+        // cannot deregister itself before it was inserted
+        let mut registered = self.tasks.lock().unwrap();
+        let task = tokio::spawn(async move {
+            let Ok(_permit) = permits.acquire_owned().await else {
+                return;
+            };
+            future.await;
+            let mut tasks = tasks.lock().unwrap();
+            if tasks.get(&task_id).is_some_and(|(registered, _)| *registered == spawn) {
+                tasks.remove(&task_id);
+            }
+        });
+        registered.insert(id, (spawn, task.abort_handle()));
+    }
+
+    /// Aborts the task handling `id`. Cancelled requests get no response.
+    pub(crate) fn cancel(&self, id: &RequestId) {
+        if let Some((_, task)) = self.tasks.lock().unwrap().remove(id) {
+            task.abort();
+        }
+    }
+
+    /// Aborts every task, e.g. because the session they belong to ended.
+    pub(crate) fn cancel_all(&self) {
+        for (_, (_, task)) in self.tasks.lock().unwrap().drain() {
+            task.abort();
+        }
+    }
+}
+
+// Result or error the client answered a server request with
+type ClientResponse = Result<serde_json::Value, McpError>;
+
+/// State negotiated with one client, shared by every request of its connection
+/// or session.
+#[derive(Clone, Default)]
+pub(crate) struct SessionState {
+    protocol_version: Arc<Mutex<ProtocolVersion>>,
+    // Set by `logging/setLevel`; no log messages are sent before that
+    log_level: Arc<Mutex<Option<LoggingLevel>>>,
+    client_capabilities: Arc<Mutex<Option<ClientCapabilities>>>,
+    // Requests sent to the client, by ID, waiting for its response
+    pending_requests: Arc<Mutex<HashMap<RequestId, oneshot::Sender<ClientResponse>>>>,
+    next_request_id: Arc<AtomicI64>,
+    // Clone of the notes repository found among the client's roots
+    local_repository: Arc<Mutex<Option<LocalRepository>>>,
+}
+
+impl SessionState {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// The revision agreed on during `initialize`, or the oldest supported one before that.
+    pub(crate) fn protocol_version(&self) -> ProtocolVersion {
+        *self.protocol_version.lock().unwrap()
+    }
+
+    fn set_protocol_version(&self, protocol_version: ProtocolVersion) {
+        *self.protocol_version.lock().unwrap() = protocol_version;
+    }
+
+    fn set_log_level(&self, level: LoggingLevel) {
+        *self.log_level.lock().unwrap() = Some(level);
+    }
+
+    // Routes log messages to `peer` at this session's level
+    fn client_sink(&self, peer: Peer) -> logging::ClientSink {
+        logging::ClientSink::new(peer, Arc::clone(&self.log_level))
+    }
+
+    fn client_capabilities(&self) -> Option<ClientCapabilities> {
+        self.client_capabilities.lock().unwrap().clone()
+    }
+
+    fn local_repository(&self) -> Option<LocalRepository> {
+        self.local_repository.lock().unwrap().clone()
+    }
+
+    /// An Invalid Request error when the negotiated revision does not allow batches.
+    pub(crate) fn batch_error(&self) -> Option<McpResponse> {
+        (!self.protocol_version().supports_batches())
+            .then(|| utils::create_invalid_request_error(RequestId::Null, errors::BATCH_NOT_SUPPORTED))
+    }
+
+    /// Sends `request` to the client and waits for the matching response.
+    pub(crate) async fn send_request(&self, peer: &Peer, request: ServerRequest) -> Result<serde_json::Value> {
+        let id = RequestId::Number(self.next_request_id.fetch_add(1, Ordering::Relaxed) + 1);
+        let (sender, receiver) = oneshot::channel();
+        self.pending_requests.lock().unwrap().insert(id.clone(), sender);
+        // Forgets the request however the wait ends, including when the
+        // request that is waiting gets cancelled
+        let _pending = PendingRequest {
+            pending_requests: &self.pending_requests,
+            id: id.clone(),
+        };
+
+        peer.send_request(&OutgoingRequest {
+            jsonrpc: "2.0".to_string(),
+            id: id.clone(),
+            request,
+        });
+
+        let timeout = Duration::from_secs(mcp_constants::CLIENT_REQUEST_TIMEOUT_SECS);
+        match tokio::time::timeout(timeout, receiver).await {
+            Ok(Ok(Ok(result))) => Ok(result),
+            Ok(Ok(Err(error))) => Err(anyhow::anyhow!("{}: {}", errors::CLIENT_REQUEST_FAILED, error.message)),
+            Ok(Err(_)) => Err(anyhow::anyhow!(errors::CLIENT_REQUEST_FAILED)),
+            Err(_) => Err(anyhow::anyhow!(errors::CLIENT_REQUEST_TIMED_OUT)),
+        }
+    }
+
+    /// Hands a client response to the request waiting for it.
+    pub(crate) fn complete_request(&self, id: RequestId, result: ClientResponse) {
+        match self.pending_requests.lock().unwrap().remove(&id) {
+            Some(sender) => {
+                let _ = sender.send(result);
+            }
+            None => logging::log(
+                LoggingLevel::Warning,
+                mcp_constants::LOGGER_SERVER,
+                serde_json::json!({ "message": "Ignoring unexpected response", "id": id }),
+            ),
+        }
+    }
+
+    /// Fails every request still waiting for a client response, e.g.
+    /// because the session ended and the response can no longer arrive.
+    pub(crate) fn abandon_client_requests(&self) {
+        self.pending_requests.lock().unwrap().clear();
+    }
+
+    // Asks the client for its roots and switches to a local clone of the
+    // notes repository when one of them is
+    async fn refresh_roots(&self, peer: &Peer) -> Result<()> {
+        let result = self.send_request(peer, ServerRequest::ListRoots).await?;
+        let result: ListRootsResult = serde_json::from_value(result)?;
+
+        let local_repository = result.roots.iter().find_map(|root| {
+            let path = reqwest::Url::parse(&root.uri).ok()?.to_file_path().ok()?;
+            LocalRepository::detect(&path, github_constants::DEFAULT_OWNER, github_constants::DEFAULT_REPO)
+        });
+        logging::log(
+            LoggingLevel::Info,
+            mcp_constants::LOGGER_SERVER,
+            match &local_repository {
+                Some(local) => serde_json::json!({ "message": "Using local clone", "path": local.root() }),
+                None => serde_json::json!({ "message": "No local clone among roots", "roots": result.roots.len() }),
+            },
+        );
+        *self.local_repository.lock().unwrap() = local_repository;
+
+        Ok(())
+    }
+}
+
+// Removes a request sent to the client from the pending ones when dropped
+struct PendingRequest<'a> {
+    pending_requests: &'a Mutex<HashMap<RequestId, oneshot::Sender<ClientResponse>>>,
+    id: RequestId,
+}
+
+impl Drop for PendingRequest<'_> {
+    fn drop(&mut self) {
+        self.pending_requests.lock().unwrap().remove(&self.id);
+    }
+}
+
+/// Per-call context handed to tool handlers.
+#[derive(Clone, Default)]
+pub struct ToolContext {
+    progress_token: Option<ProgressToken>,
+    peer: Option<Peer>,
+    session: SessionState,
+}
+
+impl ToolContext {
+    pub fn new(progress_token: Option<ProgressToken>, peer: Option<Peer>, protocol_version: ProtocolVersion) -> Self {
+        let session = SessionState::new();
+        session.set_protocol_version(protocol_version);
+        Self::for_session(progress_token, peer, session)
+    }
+
+    pub(crate) fn for_session(progress_token: Option<ProgressToken>, peer: Option<Peer>, session: SessionState) -> Self {
+        Self {
+            progress_token,
+            peer,
+            session,
+        }
+    }
+
+    /// The protocol revision negotiated with the calling client.
+    pub fn protocol_version(&self) -> ProtocolVersion {
+        self.session.protocol_version()
+    }
+
+    /// The local clone of the notes repository found among the client's roots.
+    /// Handlers should prefer it for the default branch.
+    pub fn local_repository(&self) -> Option<LocalRepository> {
+        self.session.local_repository()
+    }
+
+    /// Asks the client's model for a completion with `sampling/createMessage`.
+    /// Fails when the client did not declare the sampling capability.
+    pub async fn create_message(&self, params: CreateMessageParams) -> Result<CreateMessageResult> {
+        let supported = self.client_supports(|capabilities| capabilities.sampling.is_some());
+        let Some(peer) = self.peer.as_ref().filter(|_| supported) else {
+            return Err(anyhow::anyhow!(errors::SAMPLING_UNSUPPORTED));
+        };
+
+        let result = self
+            .session
+            .send_request(peer, ServerRequest::CreateMessage { params })
+            .await?;
+        Ok(serde_json::from_value(result)?)
+    }
+
+    /// Whether the client can be asked for input with `elicit`.
+    pub fn supports_elicitation(&self) -> bool {
+        self.peer.is_some() && self.client_supports(|capabilities| capabilities.elicitation.is_some())
+    }
+
+    /// Asks the user for input with `elicitation/create`. Fails when the
+    /// client did not declare the elicitation capability.
+    pub async fn elicit(&self, params: ElicitParams) -> Result<ElicitResult> {
+        let Some(peer) = self.peer.as_ref().filter(|_| self.supports_elicitation()) else {
+            return Err(anyhow::anyhow!(errors::ELICITATION_UNSUPPORTED));
+        };
+
+        let result = self.session.send_request(peer, ServerRequest::Elicit { params }).await?;
+        Ok(serde_json::from_value(result)?)
+    }
+
+    fn client_supports(&self, supported: impl FnOnce(&ClientCapabilities) -> bool) -> bool {
+        self.session.client_capabilities().as_ref().is_some_and(supported)
+    }
+
+    /// Emits `notifications/progress` when the client asked for progress by
+    /// sending a progress token; otherwise does nothing.
+    pub fn report_progress(&self, progress: f64, total: Option<f64>, message: Option<String>) {
+        let (Some(progress_token), Some(peer)) = (&self.progress_token, &self.peer) else {
+            return;
+        };
+
+        peer.send_notification(&ServerNotification::Progress {
+            jsonrpc: "2.0".to_string(),
+            params: ProgressParams {
+                progress_token: progress_token.clone(),
+                progress,
+                total,
+                message,
+            },
+        });
+    }
 }
 
 #[async_trait::async_trait]
 pub trait ToolHandler: Send + Sync {
-    async fn call(&self, arguments: Option<HashMap<String, serde_json::Value>>) -> Result<CallToolResult>;
+    async fn call(
+        &self,
+        arguments: Option<HashMap<String, serde_json::Value>>,
+        context: &ToolContext,
+    ) -> Result<CallToolResult>;
+
+    /// Suggests values for `argument` starting from the partially typed `value`.
+    async fn complete(&self, _argument: &str, _value: &str) -> Result<Vec<String>> {
+        Ok(Vec::new())
+    }
+}
+
+/// Tool handler with a typed argument struct. Arguments are validated and
+/// deserialized before `run` is called; every implementor is a `ToolHandler`.
+#[async_trait::async_trait]
+pub trait TypedToolHandler: Send + Sync {
+    type Arguments: DeserializeOwned + Send;
+
+    async fn run(&self, arguments: Self::Arguments, context: &ToolContext) -> Result<CallToolResult>;
+
+    /// Suggests values for `argument` starting from the partially typed `value`.
+    async fn suggest(&self, _argument: &str, _value: &str) -> Result<Vec<String>> {
+        Ok(Vec::new())
+    }
+}
+
+#[async_trait::async_trait]
+impl<H: TypedToolHandler> ToolHandler for H {
+    async fn call(
+        &self,
+        arguments: Option<HashMap<String, serde_json::Value>>,
+        context: &ToolContext,
+    ) -> Result<CallToolResult> {
+        let arguments = parse_arguments::<H::Arguments>(arguments)?;
+        self.run(arguments, context).await
+    }
+
+    async fn complete(&self, argument: &str, value: &str) -> Result<Vec<String>> {
+        self.suggest(argument, value).await
+    }
+}
+
+// Deserializes tool arguments, naming the offending field on failure
+fn parse_arguments<T: DeserializeOwned>(arguments: Option<HashMap<String, serde_json::Value>>) -> Result<T, ToolError> {
+    let value = serde_json::Value::Object(arguments.unwrap_or_default().into_iter().collect());
+
+    serde_path_to_error::deserialize(value).map_err(|e| {
+        let path = e.path().to_string();
+        let message = e.inner().to_string();
+        // Missing and unknown fields are reported at the root; their name is
+        // the first backtick-quoted word of the message
+        let name = if path == "." {
+            message.split('`').nth(1).unwrap_or_default().to_string()
+        } else {
+            path
+        };
+        ToolError::InvalidArgument { name, message }
+    })
 }
 
 #[async_trait::async_trait]
 pub trait ResourceHandler: Send + Sync {
     async fn read(&self, uri: &str) -> Result<ReadResourceResult>;
+
+    /// Suggests values for a URI template variable starting from the partially typed `value`.
+    async fn complete(&self, _argument: &str, _value: &str) -> Result<Vec<String>> {
+        Ok(Vec::new())
+    }
+}
+
+#[async_trait::async_trait]
+pub trait PromptHandler: Send + Sync {
+    async fn get(&self, arguments: Option<HashMap<String, String>>) -> Result<GetPromptResult>;
+
+    /// Suggests values for `argument` starting from the partially typed `value`.
+    async fn complete(&self, _argument: &str, _value: &str) -> Result<Vec<String>> {
+        Ok(Vec::new())
+    }
 }
 
 impl McpServer {
     pub fn new() -> Self {
         Self {
-            tools: Vec::new(),
-            resources: Vec::new(),
-            tool_handlers: HashMap::new(),
-            resource_handlers: HashMap::new(),
+            server_info: utils::create_server_info(),
+            capabilities: utils::create_server_capabilities(ProtocolVersion::LATEST),
+            transport: Transport::default(),
+            page_size: mcp_constants::DEFAULT_PAGE_SIZE,
+            handle: ServerHandle::default(),
+            resource_templates: Vec::new(),
+            resource_template_handlers: HashMap::new(),
+            prompts: Vec::new(),
+            prompt_handlers: HashMap::new(),
+            middleware: Vec::new(),
+            session: SessionState::new(),
         }
     }
 
+    pub fn builder() -> McpServerBuilder {
+        McpServerBuilder::new()
+    }
+
+    // Subscribes a new connection to list_changed notifications
+    pub(crate) fn connect(&self, peer: &Peer) {
+        self.handle.subscribe(peer);
+    }
+
+    /// Handle for changing tools and resources once the server is running.
+    pub fn handle(&self) -> ServerHandle {
+        self.handle.clone()
+    }
+
     pub fn add_tool<H>(&mut self, tool: Tool, handler: H)
     where
         H: ToolHandler + Send + Sync + 'static,
     {
-        self.tool_handlers.insert(tool.name.clone(), Box::new(handler));
-        self.tools.push(tool);
+        self.handle.add_tool(tool, handler);
     }
 
     pub fn add_resource<H>(&mut self, resource: Resource, handler: H)
     where
         H: ResourceHandler + Send + Sync + 'static,
     {
-        self.resource_handlers.insert(resource.uri.clone(), Box::new(handler));
-        self.resources.push(resource);
+        self.handle.add_resource(resource, handler);
+    }
+
+    pub fn add_resource_template<H>(&mut self, template: ResourceTemplate, handler: H)
+    where
+        H: ResourceHandler + Send + Sync + 'static,
+    {
+        self.resource_template_handlers.insert(template.uri_template.clone(), Arc::new(handler));
+        self.resource_templates.push(template);
+    }
+
+    pub fn add_prompt<H>(&mut self, prompt: Prompt, handler: H)
+    where
+        H: PromptHandler + Send + Sync + 'static,
+    {
+        self.prompt_handlers.insert(prompt.name.clone(), Box::new(handler));
+        self.prompts.push(prompt);
+    }
+
+    /// Wraps every tool call and resource read. Middleware added first runs outermost.
+    pub fn add_middleware<M>(&mut self, middleware: M)
+    where
+        M: Middleware + 'static,
+    {
+        self.middleware.push(Arc::new(middleware));
+    }
+
+    /// Completes an argument of the referenced prompt, tool or resource.
+    /// Returns `None` when nothing is registered under the reference.
+    pub async fn complete_argument(
+        &self,
+        reference: &CompletionReference,
+        argument: &CompletionArgument,
+    ) -> Option<Result<Vec<String>>> {
+        match reference {
+            CompletionReference::Prompt { name } => {
+                let handler = self.prompt_handlers.get(name)?;
+                Some(handler.complete(&argument.name, &argument.value).await)
+            }
+            CompletionReference::Tool { name } => {
+                let handler = self.handle.tool_handler(name)?;
+                Some(handler.complete(&argument.name, &argument.value).await)
+            }
+            CompletionReference::Resource { uri } => {
+                let handler = self
+                    .resource_template_handlers
+                    .get(uri)
+                    .cloned()
+                    .or_else(|| self.handle.resource_handler(uri))?;
+                Some(handler.complete(&argument.name, &argument.value).await)
+            }
+        }
+    }
+
+    // Finds the template handler whose literal prefix (up to the first variable) matches `uri`
+    fn find_resource_template_handler(&self, uri: &str) -> Option<Arc<dyn ResourceHandler + Send + Sync>> {
+        self.resource_templates
+            .iter()
+            .find(|template| {
+                let prefix = template.uri_template.split('{').next().unwrap_or_default();
+                uri.starts_with(prefix)
+            })
+            .and_then(|template| self.resource_template_handlers.get(&template.uri_template))
+            .cloned()
+    }
+
+    /// Serves clients over the configured transport (stdio unless set through the builder).
+    pub async fn run(self) -> Result<()> {
+        match self.transport {
+            Transport::Stdio => self.serve(tokio::io::stdin(), tokio::io::stdout()).await,
+            Transport::StreamableHttp(listen) => self.run_http(listen).await,
+            Transport::Sse(listen) => self.run_sse(listen).await,
+        }
+    }
+
+    /// Serves one client over any reader/writer pair, e.g. a duplex pipe in
+    /// tests. Returns once the reader reaches EOF and every response was written.
+    pub async fn serve<R, W>(self, reader: R, writer: W) -> Result<()>
+    where
+        R: AsyncRead + Unpin,
+        W: AsyncWrite + Unpin + Send + 'static,
+    {
+        Arc::new(self).serve_io(reader, writer).await
     }
 
-    pub async fn run(&self) -> Result<()> {
-        let stdin = tokio::io::stdin();
-        let reader = BufReader::new(stdin);
-        let stdout = tokio::io::stdout();
-        let mut writer = BufWriter::new(stdout);
+    /// Serves newline-delimited JSON-RPC over a reader/writer pair. Requests are
+    /// handled concurrently (bounded by `MAX_CONCURRENT_REQUESTS`) while every
+    /// outgoing message goes through a single writer task.
+    async fn serve_io<R, W>(self: Arc<Self>, reader: R, writer: W) -> Result<()>
+    where
+        R: AsyncRead + Unpin,
+        W: AsyncWrite + Unpin + Send + 'static,
+    {
+        let (sender, mut receiver) = mpsc::unbounded_channel::<String>();
+
+        let writer_task = tokio::spawn(async move {
+            let mut writer = BufWriter::new(writer);
+            while let Some(message) = receiver.recv().await {
+                writer.write_all(message.as_bytes()).await?;
+                writer.write_all(b"\n").await?;
+                writer.flush().await?;
+            }
+            Ok::<(), std::io::Error>(())
+        });
+
+        let peer = Peer::new(sender.clone());
+        let in_flight = InFlightRequests::new();
+        let session = SessionState::new();
+        self.connect(&peer);
 
-        let mut lines = reader.lines();
+        let mut lines = BufReader::new(reader).lines();
 
         while let Some(line) = lines.next_line().await? {
             if line.trim().is_empty() {
                 continue;
             }
 
-            // First try to handle as notification (no response needed)
-            if let Ok(notification) = serde_json::from_str::<crate::mcp::McpNotification>(&line) {
-                match notification {
-                    McpNotification::Initialized => {
-                        // Server is now ready to handle requests
-                        eprintln!("Server initialized successfully");
-                    }
+            match Self::parse_message(&line) {
+                IncomingMessage::Request { id, request } => {
+                    let server = Arc::clone(&self);
+                    let session = session.clone();
+                    let peer = peer.clone();
+                    in_flight.spawn(id, async move {
+                        let response = server.handle_request(request, &session, Some(peer.clone())).await;
+                        peer.send_response(&response);
+                    });
                 }
-                continue;
-            }
-            
-            // If not a notification, handle as a request
-            match self.handle_request(&line).await {
-                Ok(response) => {
-                    let response_json = serde_json::to_string(&response)?;
-                    writer.write_all(response_json.as_bytes()).await?;
-                    writer.write_all(b"\n").await?;
-                    writer.flush().await?;
-                }
-                Err(e) => {
-                    // Try to parse the request to get the ID for error response
-                    let error_response = if let Ok(value) = serde_json::from_str::<serde_json::Value>(&line) {
-                        if let Some(id) = value.get("id") {
-                            let request_id = if id.is_string() {
-                                RequestId::String(id.as_str().unwrap_or("unknown").to_string())
-                            } else if id.is_number() {
-                                RequestId::Number(id.as_i64().unwrap_or(0))
-                            } else {
-                                RequestId::Null
-                            };
-                            utils::create_parse_error(request_id, &format!("Error handling request: {}", e))
-                        } else {
-                            utils::create_parse_error(RequestId::Null, &format!("Error handling request: {}", e))
+                IncomingMessage::Notification(McpNotification::Cancelled { params }) => {
+                    in_flight.cancel(&params.request_id);
+                }
+                IncomingMessage::Notification(notification) => {
+                    self.handle_notification(notification, &session, Some(&peer)).await
+                }
+                IncomingMessage::Invalid(response) => send_message(&sender, &response),
+                IncomingMessage::Response { id, result } => session.complete_request(id, result),
+                IncomingMessage::Ignored => {}
+                IncomingMessage::Batch(_) if let Some(error) = session.batch_error() => peer.send_response(&error),
+                IncomingMessage::Batch(messages) => {
+                    let responses = self.dispatch_batch(messages, &in_flight, &session, &peer).await;
+                    let peer = peer.clone();
+                    tokio::spawn(async move {
+                        if let Some(response) = collect_batch(responses).await {
+                            peer.send_response(&response);
                         }
-                    } else {
-                        utils::create_parse_error(RequestId::Null, &format!("Error handling request: {}", e))
-                    };
-                    
-                    if let Ok(response_json) = serde_json::to_string(&error_response) {
-                        let _ = writer.write_all(response_json.as_bytes()).await;
-                        let _ = writer.write_all(b"\n").await;
-                        let _ = writer.flush().await;
-                    }
+                    });
                 }
             }
         }
 
+        // Let in-flight requests finish; the writer stops once every sender is gone
+        drop(peer);
+        drop(sender);
+        writer_task.await??;
+
         Ok(())
     }
 
-    async fn handle_request(&self, request_str: &str) -> Result<McpResponse> {
-        let request: McpRequest = serde_json::from_str(request_str)?;
+    /// Handles one incoming JSON-RPC message. Requests (messages carrying an
+    /// `id`) always produce a response; notifications never do. Batch members
+    /// are handled concurrently and answered with a single batch response.
+    pub async fn handle_message(&self, message: &str) -> Option<McpResponse> {
+        match Self::parse_message(message) {
+            IncomingMessage::Batch(_) if let Some(error) = self.session.batch_error() => Some(error),
+            IncomingMessage::Batch(messages) => {
+                let responses = join_all(messages.into_iter().map(|message| async move {
+                    match message {
+                        IncomingMessage::Request {
+                            id,
+                            request: McpRequest::Initialize { .. },
+                        } => Some(utils::create_invalid_request_error(id, errors::INITIALIZE_IN_BATCH)),
+                        message => self.handle_single_message(message).await,
+                    }
+                }))
+                .await;
+                let responses: Vec<_> = responses.into_iter().flatten().collect();
+                (!responses.is_empty()).then_some(McpResponse::Batch(responses))
+            }
+            message => self.handle_single_message(message).await,
+        }
+    }
+
+    async fn handle_single_message(&self, message: IncomingMessage) -> Option<McpResponse> {
+        match message {
+            IncomingMessage::Request { request, .. } => Some(self.handle_request(request, &self.session, None).await),
+            IncomingMessage::Notification(notification) => {
+                self.handle_notification(notification, &self.session, None).await;
+                None
+            }
+            IncomingMessage::Invalid(response) => Some(response),
+            IncomingMessage::Response { id, result } => {
+                self.session.complete_request(id, result);
+                None
+            }
+            IncomingMessage::Ignored | IncomingMessage::Batch(_) => None,
+        }
+    }
+
+    /// Dispatches the members of a batch: requests run concurrently on
+    /// `in_flight` while notifications are handled right away. The returned
+    /// receiver yields each response and closes once every request finished.
+    pub(crate) async fn dispatch_batch(
+        self: &Arc<Self>,
+        messages: Vec<IncomingMessage>,
+        in_flight: &InFlightRequests,
+        session: &SessionState,
+        peer: &Peer,
+    ) -> mpsc::UnboundedReceiver<McpResponse> {
+        let (sender, receiver) = mpsc::unbounded_channel();
 
+        for message in messages {
+            match message {
+                IncomingMessage::Request {
+                    id,
+                    request: McpRequest::Initialize { .. },
+                } => {
+                    let _ = sender.send(utils::create_invalid_request_error(id, errors::INITIALIZE_IN_BATCH));
+                }
+                IncomingMessage::Request { id, request } => {
+                    let server = Arc::clone(self);
+                    let session = session.clone();
+                    let peer = peer.clone();
+                    let sender = sender.clone();
+                    in_flight.spawn(id, async move {
+                        let response = server.handle_request(request, &session, Some(peer)).await;
+                        let _ = sender.send(response);
+                    });
+                }
+                IncomingMessage::Notification(McpNotification::Cancelled { params }) => {
+                    in_flight.cancel(&params.request_id);
+                }
+                IncomingMessage::Notification(notification) => {
+                    self.handle_notification(notification, session, Some(peer)).await
+                }
+                IncomingMessage::Invalid(response) => {
+                    let _ = sender.send(response);
+                }
+                IncomingMessage::Response { id, result } => session.complete_request(id, result),
+                IncomingMessage::Ignored | IncomingMessage::Batch(_) => {}
+            }
+        }
+
+        receiver
+    }
+
+    pub(crate) fn parse_message(message: &str) -> IncomingMessage {
+        let value: serde_json::Value = match serde_json::from_str(message) {
+            Ok(value) => value,
+            Err(e) => {
+                return IncomingMessage::Invalid(utils::create_parse_error(
+                    RequestId::Null,
+                    &format!("{}: {}", errors::INVALID_JSON, e),
+                ));
+            }
+        };
+
+        match value {
+            serde_json::Value::Array(members) if members.is_empty() => {
+                IncomingMessage::Invalid(utils::create_invalid_request_error(RequestId::Null, errors::EMPTY_BATCH))
+            }
+            serde_json::Value::Array(members) => {
+                IncomingMessage::Batch(members.into_iter().map(Self::parse_value).collect())
+            }
+            value => Self::parse_value(value),
+        }
+    }
+
+    fn parse_value(value: serde_json::Value) -> IncomingMessage {
+        if !value.is_object() {
+            return IncomingMessage::Invalid(utils::create_invalid_request_error(RequestId::Null, errors::INVALID_MESSAGE));
+        }
+
+        let Some(id) = value.get("id") else {
+            // Unknown notifications are ignored, as required by JSON-RPC
+            return match serde_json::from_value::<McpNotification>(value) {
+                Ok(notification) => IncomingMessage::Notification(notification),
+                Err(_) => IncomingMessage::Ignored,
+            };
+        };
+        let id: RequestId = serde_json::from_value(id.clone()).unwrap_or(RequestId::Null);
+
+        let Some(method) = value.get("method").and_then(|m| m.as_str()).map(str::to_string) else {
+            // A response to a request this server sent
+            if let Some(error) = value.get("error") {
+                return match serde_json::from_value::<McpError>(error.clone()) {
+                    Ok(error) => IncomingMessage::Response { id, result: Err(error) },
+                    Err(_) => IncomingMessage::Ignored,
+                };
+            }
+            if let Some(result) = value.get("result") {
+                return IncomingMessage::Response {
+                    id,
+                    result: Ok(result.clone()),
+                };
+            }
+            return IncomingMessage::Invalid(utils::create_invalid_request_error(id, errors::METHOD_REQUIRED));
+        };
+
+        if !mcp_constants::REQUEST_METHODS.contains(&method.as_str()) {
+            return IncomingMessage::Invalid(utils::create_method_not_found_error(id, &method));
+        }
+
+        match serde_json::from_value::<McpRequest>(value) {
+            Ok(request) => IncomingMessage::Request { id, request },
+            Err(e) => IncomingMessage::Invalid(utils::create_invalid_params_error(
+                id,
+                &format!("{} '{}': {}", errors::INVALID_PARAMS, method, e),
+            )),
+        }
+    }
+
+    pub(crate) async fn handle_notification(&self, notification: McpNotification, session: &SessionState, peer: Option<&Peer>) {
+        match notification {
+            McpNotification::Initialized => {
+                // Server is now ready to handle requests
+                logging::log(LoggingLevel::Info, mcp_constants::LOGGER_SERVER, serde_json::json!("Server initialized successfully"));
+                Self::spawn_roots_refresh(session, peer);
+            }
+            McpNotification::Cancelled { params } => {
+                // Transports track in-flight requests; here there is nothing to abort
+                logging::log(
+                    LoggingLevel::Debug,
+                    mcp_constants::LOGGER_SERVER,
+                    serde_json::json!({ "message": "Client cancelled request", "requestId": params.request_id }),
+                );
+            }
+            McpNotification::RootsListChanged => Self::spawn_roots_refresh(session, peer),
+        }
+    }
+
+    // Runs `roots/list` in the background when the client has roots: the
+    // response arrives through the same loop that is handling this notification
+    fn spawn_roots_refresh(session: &SessionState, peer: Option<&Peer>) {
+        let has_roots = session
+            .client_capabilities()
+            .is_some_and(|capabilities| capabilities.roots.is_some());
+        let Some(peer) = peer.filter(|_| has_roots).cloned() else {
+            return;
+        };
+
+        let session = session.clone();
+        let client = session.client_sink(peer.clone());
+        tokio::spawn(logging::scope(client, async move {
+            if let Err(e) = session.refresh_roots(&peer).await {
+                logging::log(
+                    LoggingLevel::Warning,
+                    mcp_constants::LOGGER_SERVER,
+                    serde_json::json!({ "message": "roots/list failed", "error": e.to_string() }),
+                );
+            }
+        }));
+    }
+
+    /// Answers one request. Messages logged while handling it are sent to
+    /// `peer` only, never to other sessions.
+    pub(crate) async fn handle_request(
+        &self,
+        request: McpRequest,
+        session: &SessionState,
+        peer: Option<Peer>,
+    ) -> McpResponse {
+        match peer.clone() {
+            Some(client) => logging::scope(session.client_sink(client), self.dispatch_request(request, session, peer)).await,
+            None => self.dispatch_request(request, session, peer).await,
+        }
+    }
+
+    async fn dispatch_request(
+        &self,
+        request: McpRequest,
+        session: &SessionState,
+        peer: Option<Peer>,
+    ) -> McpResponse {
         match request {
-            McpRequest::Initialize { id, params: _ } => {
+            McpRequest::Initialize { id, params } => {
+                let protocol_version = ProtocolVersion::negotiate(&params.protocol_version);
+                session.set_protocol_version(protocol_version);
+                *session.client_capabilities.lock().unwrap() = Some(params.capabilities);
+
+                let mut capabilities = self.capabilities.clone();
+                if !protocol_version.supports_completions() {
+                    capabilities.completions = None;
+                }
+
                 let result = InitializeResult {
-                    protocol_version: mcp_constants::PROTOCOL_VERSION.to_string(),
-                    capabilities: utils::create_server_capabilities(),
-                    server_info: utils::create_server_info(),
+                    protocol_version: protocol_version.as_str().to_string(),
+                    capabilities,
+                    server_info: self.server_info.for_protocol(protocol_version),
+                    instructions: self.handle.instructions(),
                 };
-                Ok(McpResponse::Initialize { 
+                McpResponse::Initialize {
                     jsonrpc: "2.0".to_string(),
-                    id, 
-                    result 
-                })
+                    id,
+                    result
+                }
             }
-            McpRequest::ListTools { id } => {
+            McpRequest::Ping { id } => McpResponse::Empty {
+                jsonrpc: "2.0".to_string(),
+                id,
+                result: EmptyResult {},
+            },
+            McpRequest::ListTools { id, params } => {
+                let protocol_version = session.protocol_version();
+                let cursor = params.and_then(|params| params.cursor);
+                let (tools, next_cursor) = match utils::paginate(self.handle.tools(), cursor.as_deref(), self.page_size) {
+                    Ok(page) => page,
+                    Err(error) => return utils::create_invalid_params_error(id, &error.to_string()),
+                };
                 let result = ListToolsResult {
-                    tools: self.tools.clone(),
+                    tools: tools
+                        .iter()
+                        .map(|tool| tool.for_protocol(protocol_version))
+                        .collect(),
+                    next_cursor,
                 };
-                Ok(McpResponse::ListTools { 
+                McpResponse::ListTools {
                     jsonrpc: "2.0".to_string(),
-                    id, 
-                    result 
-                })
+                    id,
+                    result
+                }
             }
             McpRequest::CallTool { id, params } => {
-                if let Some(handler) = self.tool_handlers.get(&params.name) {
-                    match handler.call(params.arguments).await {
-                        Ok(result) => Ok(McpResponse::CallTool { 
+                if let Some(handler) = self.handle.tool_handler(&params.name) {
+                    let progress_token = params.meta.and_then(|meta| meta.progress_token);
+                    let protocol_version = session.protocol_version();
+                    let context = ToolContext::for_session(progress_token, peer, session.clone());
+                    let call = ToolCall::new(&params.name, params.arguments, &context);
+                    let result = match ToolNext::new(&self.middleware, handler.as_ref()).run(call).await {
+                        Ok(result) => result,
+                        Err(e) => match e.downcast_ref::<ToolError>() {
+                            Some(error) if error.is_protocol_error() => {
+                                return utils::create_invalid_params_error(id, &error.to_string());
+                            }
+                            _ => utils::create_tool_error_result(&format!("{}: {}", errors::TOOL_EXECUTION_FAILED, e)),
+                        },
+                    };
+                    McpResponse::CallTool {
+                        jsonrpc: "2.0".to_string(),
+                        id,
+                        result: result.for_protocol(protocol_version),
+                    }
+                } else {
+                    utils::create_invalid_params_error(id, &format!("{}: '{}'", errors::TOOL_NOT_FOUND, params.name))
+                }
+            }
+            McpRequest::ListResources { id, params } => {
+                let cursor = params.and_then(|params| params.cursor);
+                let (resources, next_cursor) =
+                    match utils::paginate(self.handle.resources(), cursor.as_deref(), self.page_size) {
+                        Ok(page) => page,
+                        Err(error) => return utils::create_invalid_params_error(id, &error.to_string()),
+                    };
+                let result = ListResourcesResult { resources, next_cursor };
+                McpResponse::ListResources {
+                    jsonrpc: "2.0".to_string(),
+                    id,
+                    result
+                }
+            }
+            McpRequest::ReadResource { id, params } => {
+                let handler = self
+                    .handle
+                    .resource_handler(&params.uri)
+                    .or_else(|| self.find_resource_template_handler(&params.uri));
+                if let Some(handler) = handler {
+                    match ResourceNext::new(&self.middleware, handler.as_ref()).run(&params.uri).await {
+                        Ok(result) => McpResponse::ReadResource {
                             jsonrpc: "2.0".to_string(),
-                            id, 
-                            result 
-                        }),
-                        Err(e) => Ok(utils::create_internal_error(id, &format!("{}: {}", errors::TOOL_EXECUTION_FAILED, e))),
+                            id,
+                            result
+                        },
+                        Err(e) => match e.downcast_ref::<ToolError>() {
+                            Some(ToolError::NotFound(_)) => utils::create_resource_not_found_error(id, &params.uri),
+                            Some(error) if error.is_protocol_error() => {
+                                utils::create_invalid_params_error(id, &error.to_string())
+                            }
+                            _ => utils::create_internal_error(id, &format!("{}: {}", errors::RESOURCE_READ_FAILED, e)),
+                        },
                     }
                 } else {
-                    Ok(utils::create_method_not_found_error(id, &params.name))
+                    utils::create_resource_not_found_error(id, &params.uri)
                 }
             }
-            McpRequest::ListResources { id } => {
-                let result = ListResourcesResult {
-                    resources: self.resources.clone(),
+            McpRequest::ListResourceTemplates { id } => {
+                let result = ListResourceTemplatesResult {
+                    resource_templates: self.resource_templates.clone(),
                 };
-                Ok(McpResponse::ListResources { 
+                McpResponse::ListResourceTemplates {
                     jsonrpc: "2.0".to_string(),
-                    id, 
-                    result 
-                })
+                    id,
+                    result
+                }
             }
-            McpRequest::ReadResource { id, params } => {
-                if let Some(handler) = self.resource_handlers.get(&params.uri) {
-                    match handler.read(&params.uri).await {
-                        Ok(result) => Ok(McpResponse::ReadResource { 
+            McpRequest::ListPrompts { id } => {
+                let result = ListPromptsResult {
+                    prompts: self.prompts.clone(),
+                };
+                McpResponse::ListPrompts {
+                    jsonrpc: "2.0".to_string(),
+                    id,
+                    result
+                }
+            }
+            McpRequest::GetPrompt { id, params } => {
+                if let Some(handler) = self.prompt_handlers.get(&params.name) {
+                    match handler.get(params.arguments).await {
+                        Ok(result) => McpResponse::GetPrompt {
                             jsonrpc: "2.0".to_string(),
-                            id, 
-                            result 
-                        }),
-                        Err(e) => Ok(utils::create_internal_error(id, &format!("{}: {}", errors::RESOURCE_READ_FAILED, e))),
+                            id,
+                            result
+                        },
+                        Err(e) => match e.downcast_ref::<ToolError>() {
+                            Some(error) if error.is_protocol_error() => {
+                                utils::create_invalid_params_error(id, &error.to_string())
+                            }
+                            _ => utils::create_internal_error(id, &format!("{}: {}", errors::PROMPT_GET_FAILED, e)),
+                        },
                     }
                 } else {
-                    Ok(utils::create_method_not_found_error(id, &params.uri))
+                    utils::create_invalid_params_error(id, &format!("{}: '{}'", errors::PROMPT_NOT_FOUND, params.name))
+                }
+            }
+            McpRequest::SetLevel { id, params } => {
+                session.set_log_level(params.level);
+                McpResponse::Empty {
+                    jsonrpc: "2.0".to_string(),
+                    id,
+                    result: EmptyResult {},
                 }
             }
+            McpRequest::Complete { id, params } => {
+                let Some(completion) = self.complete_argument(&params.reference, &params.argument).await else {
+                    return utils::create_invalid_params_error(id, &format!("{}: {:?}", errors::UNKNOWN_COMPLETION_REFERENCE, params.reference));
+                };
+                match completion {
+                    Ok(values) => McpResponse::Complete {
+                        jsonrpc: "2.0".to_string(),
+                        id,
+                        result: CompleteResult {
+                            completion: utils::create_completion(values),
+                        },
+                    },
+                    Err(e) => utils::create_internal_error(id, &format!("{}: {}", errors::COMPLETION_FAILED, e)),
+                }
+            }
+        }
+    }
+}
+
+impl Default for McpServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Configures an `McpServer` for embedding: identity, capabilities, transport,
+/// handlers and middleware.
+pub struct McpServerBuilder {
+    server: McpServer,
+}
+
+impl McpServerBuilder {
+    pub fn new() -> Self {
+        Self {
+            server: McpServer::new(),
+        }
+    }
+
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.server.server_info.name = name.into();
+        self
+    }
+
+    pub fn version(mut self, version: impl Into<String>) -> Self {
+        self.server.server_info.version = version.into();
+        self
+    }
+
+    /// Display name shown by clients on 2025-06-18 and later.
+    pub fn title(mut self, title: impl Into<String>) -> Self {
+        self.server.server_info.title = Some(title.into());
+        self
+    }
+
+    pub fn website_url(mut self, website_url: impl Into<String>) -> Self {
+        self.server.server_info.website_url = Some(website_url.into());
+        self
+    }
+
+    pub fn instructions(self, instructions: impl Into<String>) -> Self {
+        self.server.handle.set_instructions(instructions);
+        self
+    }
+
+    /// Replaces the advertised capabilities. `completions` is still withheld
+    /// from clients on revisions that predate it.
+    pub fn capabilities(mut self, capabilities: ServerCapabilities) -> Self {
+        self.server.capabilities = capabilities;
+        self
+    }
+
+    pub fn transport(mut self, transport: Transport) -> Self {
+        self.server.transport = transport;
+        self
+    }
+
+    /// Number of tools or resources per page of `tools/list` and `resources/list`.
+    pub fn page_size(mut self, page_size: usize) -> Self {
+        self.server.page_size = page_size;
+        self
+    }
+
+    pub fn tool<H>(mut self, tool: Tool, handler: H) -> Self
+    where
+        H: ToolHandler + Send + Sync + 'static,
+    {
+        self.server.add_tool(tool, handler);
+        self
+    }
+
+    pub fn resource<H>(mut self, resource: Resource, handler: H) -> Self
+    where
+        H: ResourceHandler + Send + Sync + 'static,
+    {
+        self.server.add_resource(resource, handler);
+        self
+    }
+
+    pub fn resource_template<H>(mut self, template: ResourceTemplate, handler: H) -> Self
+    where
+        H: ResourceHandler + Send + Sync + 'static,
+    {
+        self.server.add_resource_template(template, handler);
+        self
+    }
+
+    pub fn prompt<H>(mut self, prompt: Prompt, handler: H) -> Self
+    where
+        H: PromptHandler + Send + Sync + 'static,
+    {
+        self.server.add_prompt(prompt, handler);
+        self
+    }
+
+    pub fn middleware<M>(mut self, middleware: M) -> Self
+    where
+        M: Middleware + 'static,
+    {
+        self.server.add_middleware(middleware);
+        self
+    }
+
+    pub fn build(self) -> McpServer {
+        self.server
+    }
+}
+
+impl Default for McpServerBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Waits for every response of a batch. Batches made up only of
+/// notifications (or cancelled requests) get no response at all.
+pub(crate) async fn collect_batch(mut receiver: mpsc::UnboundedReceiver<McpResponse>) -> Option<McpResponse> {
+    let mut responses = Vec::new();
+    while let Some(response) = receiver.recv().await {
+        responses.push(response);
+    }
+
+    (!responses.is_empty()).then_some(McpResponse::Batch(responses))
+}
+
+fn send_message<T: serde::Serialize>(sender: &mpsc::UnboundedSender<String>, message: &T) {
+    match serde_json::to_string(message) {
+        // The receiver only goes away once the connection is shutting down
+        Ok(json) => {
+            let _ = sender.send(json);
+        }
+        Err(e) => logging::log(
+            LoggingLevel::Error,
+            mcp_constants::LOGGER_SERVER,
+            serde_json::json!({ "message": "Failed to serialize outgoing message", "error": e.to_string() }),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    fn create_test_tool(name: &str, description: &str) -> Tool {
+        Tool {
+            name: name.to_string(),
+            title: None,
+            description: description.to_string(),
+            input_schema: serde_json::json!({ "type": "object" }),
+            output_schema: None,
+            annotations: None,
+        }
+    }
+
+    struct SlowToolHandler;
+
+    #[async_trait::async_trait]
+    impl ToolHandler for SlowToolHandler {
+        async fn call(
+            &self,
+            _arguments: Option<HashMap<String, serde_json::Value>>,
+            context: &ToolContext,
+        ) -> Result<CallToolResult> {
+            context.report_progress(0.0, Some(1.0), Some("Sleeping".to_string()));
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            Ok(CallToolResult {
+                content: vec![ToolContent::Text { text: "done".to_string() }],
+                structured_content: None,
+                is_error: None,
+            })
+        }
+    }
+
+    // Fails with the error named by its `error` argument
+    struct FailingToolHandler;
+
+    #[async_trait::async_trait]
+    impl ToolHandler for FailingToolHandler {
+        async fn call(
+            &self,
+            arguments: Option<HashMap<String, serde_json::Value>>,
+            _context: &ToolContext,
+        ) -> Result<CallToolResult> {
+            match arguments.as_ref().and_then(|args| args.get("error")).and_then(|v| v.as_str()) {
+                Some("argument") => Err(ToolError::invalid_argument("path", "Path parameter is required").into()),
+                _ => Err(ToolError::NotFound("notes/missing.md".to_string()).into()),
+            }
+        }
+    }
+
+    // Reports where the calling session's notes come from
+    struct BackendProbeHandler;
+
+    #[async_trait::async_trait]
+    impl ToolHandler for BackendProbeHandler {
+        async fn call(
+            &self,
+            _arguments: Option<HashMap<String, serde_json::Value>>,
+            context: &ToolContext,
+        ) -> Result<CallToolResult> {
+            let backend = match context.local_repository() {
+                Some(local) => local.root().display().to_string(),
+                None => "github".to_string(),
+            };
+            Ok(CallToolResult {
+                content: vec![ToolContent::Text { text: backend }],
+                structured_content: None,
+                is_error: None,
+            })
+        }
+    }
+
+    // Returns the client model's summary of a fixed note
+    struct SamplingToolHandler;
+
+    #[async_trait::async_trait]
+    impl ToolHandler for SamplingToolHandler {
+        async fn call(
+            &self,
+            _arguments: Option<HashMap<String, serde_json::Value>>,
+            context: &ToolContext,
+        ) -> Result<CallToolResult> {
+            let result = context
+                .create_message(utils::create_summary_request("notes/day.md", "Shipped sampling."))
+                .await?;
+            let text = match result.content {
+                SamplingContent::Text { text } => format!("{}: {}", result.model, text),
+                SamplingContent::Other => String::new(),
+            };
+            Ok(CallToolResult {
+                content: vec![ToolContent::Text { text }],
+                structured_content: None,
+                is_error: None,
+            })
+        }
+    }
+
+    fn create_slow_server() -> McpServer {
+        let mut server = McpServer::new();
+        server.add_tool(create_test_tool("slow", "Sleeps before answering"), SlowToolHandler);
+        server
+    }
+
+    #[tokio::test]
+    async fn test_slow_request_does_not_block_ping() {
+        // Separate pipes per direction so dropping the client writer signals EOF
+        let (mut client_writer, server_reader) = tokio::io::duplex(4096);
+        let (server_writer, client_reader) = tokio::io::duplex(4096);
+        let server = tokio::spawn(Arc::new(create_slow_server()).serve_io(server_reader, server_writer));
+
+        client_writer
+            .write_all(b"{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"tools/call\",\"params\":{\"name\":\"slow\"}}\n")
+            .await
+            .unwrap();
+        client_writer
+            .write_all(b"{\"jsonrpc\":\"2.0\",\"id\":2,\"method\":\"ping\"}\n")
+            .await
+            .unwrap();
+
+        let mut lines = BufReader::new(client_reader).lines();
+        let first: serde_json::Value = serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+        let second: serde_json::Value = serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+
+        assert_eq!(first["id"], 2);
+        assert_eq!(second["id"], 1);
+        assert_eq!(second["result"]["content"][0]["text"], "done");
+
+        drop(client_writer);
+        server.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_request_gets_no_response() {
+        // Separate pipes per direction so dropping the client writer signals EOF
+        let (mut client_writer, server_reader) = tokio::io::duplex(4096);
+        let (server_writer, client_reader) = tokio::io::duplex(4096);
+        let server = tokio::spawn(Arc::new(create_slow_server()).serve_io(server_reader, server_writer));
+
+        client_writer
+            .write_all(b"{\"jsonrpc\":\"2.0\",\"id\":\"slow-1\",\"method\":\"tools/call\",\"params\":{\"name\":\"slow\"}}\n")
+            .await
+            .unwrap();
+        client_writer
+            .write_all(b"{\"jsonrpc\":\"2.0\",\"method\":\"notifications/cancelled\",\"params\":{\"requestId\":\"slow-1\"}}\n")
+            .await
+            .unwrap();
+        client_writer
+            .write_all(b"{\"jsonrpc\":\"2.0\",\"id\":3,\"method\":\"ping\"}\n")
+            .await
+            .unwrap();
+        drop(client_writer);
+
+        let mut lines = BufReader::new(client_reader).lines();
+        let mut responses = Vec::new();
+        while let Some(line) = lines.next_line().await.unwrap() {
+            responses.push(serde_json::from_str::<serde_json::Value>(&line).unwrap());
+        }
+
+        assert_eq!(responses.len(), 1);
+        assert_eq!(responses[0]["id"], 3);
+        server.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_progress_notifications_follow_progress_token() {
+        let (mut client_writer, server_reader) = tokio::io::duplex(4096);
+        let (server_writer, client_reader) = tokio::io::duplex(4096);
+        let server = tokio::spawn(Arc::new(create_slow_server()).serve_io(server_reader, server_writer));
+
+        client_writer
+            .write_all(b"{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"tools/call\",\"params\":{\"name\":\"slow\",\"_meta\":{\"progressToken\":\"tok\"}}}\n")
+            .await
+            .unwrap();
+        client_writer
+            .write_all(b"{\"jsonrpc\":\"2.0\",\"id\":2,\"method\":\"tools/call\",\"params\":{\"name\":\"slow\"}}\n")
+            .await
+            .unwrap();
+        drop(client_writer);
+
+        let mut lines = BufReader::new(client_reader).lines();
+        let mut messages = Vec::new();
+        while let Some(line) = lines.next_line().await.unwrap() {
+            messages.push(serde_json::from_str::<serde_json::Value>(&line).unwrap());
+        }
+
+        let progress: Vec<_> = messages
+            .iter()
+            .filter(|message| message["method"] == "notifications/progress")
+            .collect();
+        assert_eq!(progress.len(), 1);
+        assert_eq!(progress[0]["params"]["progressToken"], "tok");
+        assert_eq!(progress[0]["params"]["total"], 1.0);
+        assert_eq!(messages.len(), 3);
+        server.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_runtime_registration_notifies_clients() {
+        let server = create_slow_server();
+        let handle = server.handle();
+        let (mut client_writer, server_reader) = tokio::io::duplex(4096);
+        let (server_writer, client_reader) = tokio::io::duplex(4096);
+        let serving = tokio::spawn(Arc::new(server).serve_io(server_reader, server_writer));
+        let mut lines = BufReader::new(client_reader).lines();
+
+        // The connection is subscribed once it answers
+        client_writer
+            .write_all(b"{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"ping\"}\n")
+            .await
+            .unwrap();
+        lines.next_line().await.unwrap().unwrap();
+
+        handle.add_resource(
+            utils::create_note_file_resource("pinned.md"),
+            crate::handlers::NoteFileResourceHandler::new(),
+        );
+        let message: serde_json::Value = serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+        assert_eq!(message["method"], "notifications/resources/list_changed");
+
+        client_writer
+            .write_all(b"{\"jsonrpc\":\"2.0\",\"id\":2,\"method\":\"resources/list\"}\n")
+            .await
+            .unwrap();
+        let message: serde_json::Value = serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+        assert_eq!(message["result"]["resources"][0]["uri"], "notion://file/pinned.md");
+
+        assert!(handle.remove_tool("slow"));
+        assert!(!handle.remove_tool("slow"));
+        let message: serde_json::Value = serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+        assert_eq!(message["method"], "notifications/tools/list_changed");
+        assert!(handle.tool_names().is_empty());
+
+        drop(client_writer);
+        assert!(lines.next_line().await.unwrap().is_none());
+        serving.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_roots_select_local_clone() {
+        let clone = crate::local::create_test_clone("https://github.com/ParkJong-Hun/my-notion.git", &[]);
+        let clone_uri = reqwest::Url::from_file_path(&clone).unwrap().to_string();
+
+        let mut server = McpServer::new();
+        server.add_tool(create_test_tool("probe", "Reports the backend"), BackendProbeHandler);
+        let (mut client_writer, server_reader) = tokio::io::duplex(4096);
+        let (server_writer, client_reader) = tokio::io::duplex(4096);
+        let serving = tokio::spawn(Arc::new(server).serve_io(server_reader, server_writer));
+        let mut lines = BufReader::new(client_reader).lines();
+
+        client_writer
+            .write_all(b"{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"initialize\",\"params\":{\"protocolVersion\":\"2025-06-18\",\"capabilities\":{\"roots\":{\"listChanged\":true}}}}\n")
+            .await
+            .unwrap();
+        lines.next_line().await.unwrap().unwrap();
+        client_writer
+            .write_all(b"{\"jsonrpc\":\"2.0\",\"method\":\"notifications/initialized\"}\n")
+            .await
+            .unwrap();
+
+        let request: serde_json::Value = serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+        assert_eq!(request["method"], "roots/list");
+        let response = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": request["id"],
+            "result": { "roots": [{ "uri": "file:///elsewhere" }, { "uri": clone_uri, "name": "notes" }] }
+        });
+        client_writer
+            .write_all(format!("{}\n", response).as_bytes())
+            .await
+            .unwrap();
+
+        // The roots are applied in the background; poll until the session switched
+        let mut backend = String::new();
+        for id in 2..50 {
+            let call = serde_json::json!({ "jsonrpc": "2.0", "id": id, "method": "tools/call", "params": { "name": "probe" } });
+            client_writer.write_all(format!("{}\n", call).as_bytes()).await.unwrap();
+            let response: serde_json::Value = serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+            backend = response["result"]["content"][0]["text"].as_str().unwrap().to_string();
+            if backend != "github" {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(backend, clone.display().to_string());
+
+        drop(client_writer);
+        serving.await.unwrap().unwrap();
+        std::fs::remove_dir_all(clone).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_sampling_request_is_answered_by_client() {
+        let error = ToolContext::default()
+            .create_message(utils::create_summary_request("notes/day.md", ""))
+            .await
+            .unwrap_err();
+        assert_eq!(error.to_string(), errors::SAMPLING_UNSUPPORTED);
+
+        let mut server = McpServer::new();
+        server.add_tool(create_test_tool("sample", "Summarizes a note"), SamplingToolHandler);
+        let (mut client_writer, server_reader) = tokio::io::duplex(4096);
+        let (server_writer, client_reader) = tokio::io::duplex(4096);
+        let serving = tokio::spawn(Arc::new(server).serve_io(server_reader, server_writer));
+        let mut lines = BufReader::new(client_reader).lines();
+
+        client_writer
+            .write_all(b"{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"initialize\",\"params\":{\"protocolVersion\":\"2025-06-18\",\"capabilities\":{\"sampling\":{}}}}\n")
+            .await
+            .unwrap();
+        lines.next_line().await.unwrap().unwrap();
+        client_writer
+            .write_all(b"{\"jsonrpc\":\"2.0\",\"id\":2,\"method\":\"tools/call\",\"params\":{\"name\":\"sample\"}}\n")
+            .await
+            .unwrap();
+
+        let request: serde_json::Value = serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+        assert_eq!(request["method"], "sampling/createMessage");
+        assert_eq!(request["params"]["maxTokens"], mcp_constants::SUMMARY_MAX_TOKENS);
+        assert!(request["params"]["messages"][0]["content"]["text"].as_str().unwrap().contains("Shipped sampling."));
+        let response = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": request["id"],
+            "result": { "role": "assistant", "content": { "type": "text", "text": "A short day." }, "model": "test-model" }
+        });
+        client_writer
+            .write_all(format!("{}\n", response).as_bytes())
+            .await
+            .unwrap();
+
+        let response: serde_json::Value = serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+        assert_eq!(response["id"], 2);
+        assert_eq!(response["result"]["content"][0]["text"], "test-model: A short day.");
+
+        drop(client_writer);
+        serving.await.unwrap().unwrap();
+    }
+
+    type ClientLines = tokio::io::Lines<BufReader<tokio::io::DuplexStream>>;
+
+    async fn send_line(writer: &mut tokio::io::DuplexStream, message: serde_json::Value) {
+        writer.write_all(format!("{}\n", message).as_bytes()).await.unwrap();
+    }
+
+    async fn read_line(lines: &mut ClientLines) -> serde_json::Value {
+        serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap()
+    }
+
+    // Starts a session whose only root is `clone` and waits until the server
+    // reads notes from it; the server needs the `probe` tool
+    async fn connect_to_clone(
+        server: Arc<McpServer>,
+        clone: &std::path::Path,
+        capabilities: serde_json::Value,
+    ) -> (tokio::io::DuplexStream, ClientLines) {
+        let (mut client_writer, server_reader) = tokio::io::duplex(4096);
+        let (server_writer, client_reader) = tokio::io::duplex(4096);
+        tokio::spawn(server.serve_io(server_reader, server_writer));
+        let mut lines = BufReader::new(client_reader).lines();
+
+        let initialize = serde_json::json!({ "jsonrpc": "2.0", "id": 0, "method": "initialize", "params": { "protocolVersion": "2025-06-18", "capabilities": capabilities } });
+        send_line(&mut client_writer, initialize).await;
+        read_line(&mut lines).await;
+        send_line(&mut client_writer, serde_json::json!({ "jsonrpc": "2.0", "method": "notifications/initialized" })).await;
+
+        let request = read_line(&mut lines).await;
+        let uri = reqwest::Url::from_file_path(clone).unwrap().to_string();
+        send_line(&mut client_writer, serde_json::json!({ "jsonrpc": "2.0", "id": request["id"], "result": { "roots": [{ "uri": uri }] } })).await;
+
+        for id in 1..50 {
+            let call = serde_json::json!({ "jsonrpc": "2.0", "id": format!("probe-{}", id), "method": "tools/call", "params": { "name": "probe" } });
+            send_line(&mut client_writer, call).await;
+            if read_line(&mut lines).await["result"]["content"][0]["text"] != "github" {
+                return (client_writer, lines);
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
         }
+        panic!("the session never switched to the local clone");
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_missing_path_is_resolved_by_elicitation() {
+        let clone = crate::local::create_test_clone(
+            "https://github.com/ParkJong-Hun/my-notion.git",
+            &[("notes/meeting-2025-01-06.md", "# First meeting\n"), ("notes/meeting-2025-01-13.md", "# Second meeting\n")],
+        );
+
+        let mut server = McpServer::new();
+        server.add_tool(create_test_tool("probe", "Reports the backend"), BackendProbeHandler);
+        server.add_tool(utils::create_get_file_content_tool(), crate::handlers::GetFileContentHandler::new());
+        let server = Arc::new(server);
+        let call = |id: i64| serde_json::json!({ "jsonrpc": "2.0", "id": id, "method": "tools/call", "params": { "name": "get_file_content", "arguments": { "path": "notes/meeting.md" } } });
+
+        let (mut client_writer, mut lines) =
+            connect_to_clone(Arc::clone(&server), &clone, serde_json::json!({ "roots": {}, "elicitation": {} })).await;
+
+        send_line(&mut client_writer, call(100)).await;
+        let request = read_line(&mut lines).await;
+        assert_eq!(request["method"], "elicitation/create");
+        let choices = &request["params"]["requestedSchema"]["properties"]["path"]["enum"];
+        assert_eq!(choices, &serde_json::json!(["notes/meeting-2025-01-06.md", "notes/meeting-2025-01-13.md"]));
+        let answer = serde_json::json!({ "action": "accept", "content": { "path": "notes/meeting-2025-01-13.md" } });
+        send_line(&mut client_writer, serde_json::json!({ "jsonrpc": "2.0", "id": request["id"], "result": answer })).await;
+        let response = read_line(&mut lines).await;
+        assert_eq!(response["id"], 100);
+        assert_eq!(response["result"]["structuredContent"]["content"], "# Second meeting\n");
+
+        send_line(&mut client_writer, call(101)).await;
+        let request = read_line(&mut lines).await;
+        send_line(&mut client_writer, serde_json::json!({ "jsonrpc": "2.0", "id": request["id"], "result": { "action": "decline" } })).await;
+        let response = read_line(&mut lines).await;
+        assert_eq!(response["result"]["isError"], true);
+
+        // Without elicitation, the suggestions are part of the failure
+        let (mut client_writer, mut lines) =
+            connect_to_clone(Arc::clone(&server), &clone, serde_json::json!({ "roots": {} })).await;
+        send_line(&mut client_writer, call(102)).await;
+        let response = read_line(&mut lines).await;
+        assert_eq!(response["result"]["isError"], true);
+        let text = response["result"]["content"][0]["text"].as_str().unwrap();
+        assert!(text.contains("did you mean notes/meeting-2025-01-06.md, notes/meeting-2025-01-13.md"));
+
+        std::fs::remove_dir_all(clone).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_reused_request_id_stays_cancellable() {
+        let in_flight = InFlightRequests::new();
+        let (first_done, first_finished) = oneshot::channel();
+        let (second_done, second_finished) = oneshot::channel::<()>();
+
+        in_flight.spawn(RequestId::Number(1), async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            let _ = first_done.send(());
+        });
+        in_flight.spawn(RequestId::Number(1), async move {
+            tokio::time::sleep(Duration::from_secs(10)).await;
+            let _ = second_done.send(());
+        });
+
+        // The first task finishing must not forget the second one
+        first_finished.await.unwrap();
+        tokio::task::yield_now().await;
+        in_flight.cancel(&RequestId::Number(1));
+        assert!(second_finished.await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_request_forgets_its_client_request() {
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let peer = Peer::new(sender);
+        let session = SessionState::new();
+        let in_flight = InFlightRequests::new();
+
+        let waiting = session.clone();
+        in_flight.spawn(RequestId::Number(7), async move {
+            let _ = waiting.send_request(&peer, ServerRequest::ListRoots).await;
+        });
+        receiver.recv().await.unwrap();
+        assert_eq!(session.pending_requests.lock().unwrap().len(), 1);
+
+        in_flight.cancel(&RequestId::Number(7));
+        tokio::task::yield_now().await;
+        assert!(session.pending_requests.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_batch_members_run_concurrently() {
+        let (mut client_writer, server_reader) = tokio::io::duplex(4096);
+        let (server_writer, client_reader) = tokio::io::duplex(4096);
+        let server = tokio::spawn(Arc::new(create_slow_server()).serve_io(server_reader, server_writer));
+
+        let started = std::time::Instant::now();
+        client_writer
+            .write_all(b"[{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"tools/call\",\"params\":{\"name\":\"slow\"}},{\"jsonrpc\":\"2.0\",\"id\":2,\"method\":\"tools/call\",\"params\":{\"name\":\"slow\"}},{\"jsonrpc\":\"2.0\",\"method\":\"notifications/initialized\"}]\n")
+            .await
+            .unwrap();
+        drop(client_writer);
+
+        let mut lines = BufReader::new(client_reader).lines();
+        let batch: serde_json::Value = serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+        assert!(started.elapsed() < Duration::from_millis(550));
+        assert_eq!(batch.as_array().unwrap().len(), 2);
+        assert!(lines.next_line().await.unwrap().is_none());
+        server.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_tool_failures_map_to_results_or_protocol_errors() {
+        let mut server = McpServer::new();
+        server.add_tool(create_test_tool("failing", "Always fails"), FailingToolHandler);
+
+        let response = server
+            .handle_message(r#"{"jsonrpc":"2.0","id":1,"method":"tools/call","params":{"name":"failing"}}"#)
+            .await
+            .unwrap();
+        let serialized = serde_json::to_value(&response).unwrap();
+        assert_eq!(serialized["result"]["isError"], true);
+        assert!(serialized["result"]["content"][0]["text"].as_str().unwrap().contains("notes/missing.md"));
+
+        let response = server
+            .handle_message(r#"{"jsonrpc":"2.0","id":2,"method":"tools/call","params":{"name":"failing","arguments":{"error":"argument"}}}"#)
+            .await
+            .unwrap();
+        let serialized = serde_json::to_value(&response).unwrap();
+        assert_eq!(serialized["error"]["code"], -32602);
+        assert!(serialized["error"]["message"].as_str().unwrap().contains("'path'"));
+    }
+}
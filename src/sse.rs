@@ -0,0 +1,257 @@
+use crate::constants::{errors, http as http_constants, mcp as mcp_constants};
+use crate::logging;
+use crate::mcp::*;
+use crate::server::{collect_batch, InFlightRequests, IncomingMessage, McpServer, Peer, SessionState};
+use crate::streamable_http::{message_events, origin_allowed};
+use anyhow::Result;
+use axum::extract::{Query, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::Router;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::UnboundedReceiverStream;
+use tokio_stream::StreamExt;
+
+/// One legacy SSE connection. Every response and notification for the
+/// session is written to its event stream.
+struct SseSession {
+    in_flight: InFlightRequests,
+    peer: Peer,
+    state: SessionState,
+}
+
+struct SseState {
+    server: Arc<McpServer>,
+    sessions: Mutex<HashMap<String, Arc<SseSession>>>,
+}
+
+// Removes the session once its event stream is dropped, i.e. the client
+// disconnected, and stops its work since no result can reach the client anymore
+struct SessionGuard {
+    state: Arc<SseState>,
+    session_id: String,
+}
+
+impl Drop for SessionGuard {
+    fn drop(&mut self) {
+        if let Some(session) = self.state.sessions.lock().unwrap().remove(&self.session_id) {
+            session.in_flight.cancel_all();
+            session.state.abandon_client_requests();
+        }
+    }
+}
+
+impl McpServer {
+    /// Serves the 2024-11-05 HTTP+SSE transport on `listen`: clients open an
+    /// event stream with GET on the SSE endpoint, receive the URL to POST
+    /// messages to in an `endpoint` event, and read every response from the stream.
+    pub async fn run_sse(self, listen: SocketAddr) -> Result<()> {
+        let state = Arc::new(SseState {
+            server: Arc::new(self),
+            sessions: Mutex::new(HashMap::new()),
+        });
+
+        let app = Router::new()
+            .route(http_constants::SSE_ENDPOINT, get(handle_sse))
+            .route(http_constants::MESSAGES_ENDPOINT, post(handle_message))
+            .with_state(state);
+
+        let listener = tokio::net::TcpListener::bind(listen).await?;
+        logging::log(
+            LoggingLevel::Info,
+            mcp_constants::LOGGER_SERVER,
+            serde_json::json!(format!("Listening on http://{}{}", listener.local_addr()?, http_constants::SSE_ENDPOINT)),
+        );
+        axum::serve(listener, app).await?;
+
+        Ok(())
+    }
+}
+
+async fn handle_sse(State(state): State<Arc<SseState>>, headers: HeaderMap) -> Response {
+    if !origin_allowed(&headers) {
+        return (StatusCode::FORBIDDEN, errors::ORIGIN_NOT_ALLOWED).into_response();
+    }
+
+    let session_id = uuid::Uuid::new_v4().to_string();
+    let (sender, receiver) = mpsc::unbounded_channel();
+    let session = Arc::new(SseSession {
+        in_flight: InFlightRequests::new(),
+        peer: Peer::new(sender),
+        state: SessionState::new(),
+    });
+    state.server.connect(&session.peer);
+    state
+        .sessions
+        .lock()
+        .unwrap()
+        .insert(session_id.clone(), session);
+
+    let endpoint = Event::default().event("endpoint").data(format!(
+        "{}?{}={}",
+        http_constants::MESSAGES_ENDPOINT,
+        http_constants::SESSION_ID_QUERY,
+        session_id
+    ));
+    let guard = SessionGuard {
+        state: Arc::clone(&state),
+        session_id,
+    };
+
+    let messages = message_events(UnboundedReceiverStream::new(receiver)).map(move |event| {
+        let _ = &guard;
+        event
+    });
+
+    Sse::new(tokio_stream::once(Ok(endpoint)).chain(messages))
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+async fn handle_message(
+    State(state): State<Arc<SseState>>,
+    Query(query): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+    body: String,
+) -> Response {
+    if !origin_allowed(&headers) {
+        return (StatusCode::FORBIDDEN, errors::ORIGIN_NOT_ALLOWED).into_response();
+    }
+
+    let Some(session_id) = query.get(http_constants::SESSION_ID_QUERY) else {
+        return (StatusCode::BAD_REQUEST, errors::SESSION_ID_QUERY_REQUIRED).into_response();
+    };
+    let Some(session) = state.sessions.lock().unwrap().get(session_id).cloned() else {
+        return (StatusCode::NOT_FOUND, errors::SESSION_NOT_FOUND).into_response();
+    };
+
+    match McpServer::parse_message(&body) {
+        IncomingMessage::Request { id, request } => {
+            let server = Arc::clone(&state.server);
+            let session_state = session.state.clone();
+            let peer = session.peer.clone();
+            session.in_flight.spawn(id, async move {
+                let response = server.handle_request(request, &session_state, Some(peer.clone())).await;
+                peer.send_response(&response);
+            });
+        }
+        IncomingMessage::Notification(McpNotification::Cancelled { params }) => {
+            session.in_flight.cancel(&params.request_id);
+        }
+        IncomingMessage::Notification(notification) => {
+            state
+                .server
+                .handle_notification(notification, &session.state, Some(&session.peer))
+                .await
+        }
+        IncomingMessage::Invalid(response) => session.peer.send_response(&response),
+        IncomingMessage::Response { id, result } => session.state.complete_request(id, result),
+        IncomingMessage::Ignored => {}
+        IncomingMessage::Batch(_) if let Some(error) = session.state.batch_error() => session.peer.send_response(&error),
+        IncomingMessage::Batch(messages) => {
+            let responses = state
+                .server
+                .dispatch_batch(messages, &session.in_flight, &session.state, &session.peer)
+                .await;
+            let peer = session.peer.clone();
+            tokio::spawn(async move {
+                if let Some(response) = collect_batch(responses).await {
+                    peer.send_response(&response);
+                }
+            });
+        }
+    }
+
+    StatusCode::ACCEPTED.into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_messages_are_answered_on_the_event_stream() {
+        let state = Arc::new(SseState {
+            server: Arc::new(McpServer::new()),
+            sessions: Mutex::new(HashMap::new()),
+        });
+
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        state.sessions.lock().unwrap().insert(
+            "session".to_string(),
+            Arc::new(SseSession {
+                in_flight: InFlightRequests::new(),
+                peer: Peer::new(sender),
+                state: SessionState::new(),
+            }),
+        );
+
+        let mut query = HashMap::new();
+        query.insert(http_constants::SESSION_ID_QUERY.to_string(), "session".to_string());
+        let response = handle_message(
+            State(Arc::clone(&state)),
+            Query(query),
+            HeaderMap::new(),
+            r#"{"jsonrpc":"2.0","id":9,"method":"ping"}"#.to_string(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+
+        let message: serde_json::Value = serde_json::from_str(&receiver.recv().await.unwrap()).unwrap();
+        assert_eq!(message["id"], 9);
+        assert_eq!(message["result"], serde_json::json!({}));
+    }
+
+    #[tokio::test]
+    async fn test_disconnect_cancels_running_requests() {
+        let state = Arc::new(SseState {
+            server: Arc::new(McpServer::new()),
+            sessions: Mutex::new(HashMap::new()),
+        });
+
+        let stream = handle_sse(State(Arc::clone(&state)), HeaderMap::new()).await;
+        let session = state.sessions.lock().unwrap().values().next().cloned().unwrap();
+        let (running, stopped) = tokio::sync::oneshot::channel::<()>();
+        session.in_flight.spawn(RequestId::Number(1), async move {
+            let _running = running;
+            std::future::pending::<()>().await;
+        });
+
+        drop(stream);
+        assert!(state.sessions.lock().unwrap().is_empty());
+        assert!(stopped.await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_unknown_session_is_rejected() {
+        let state = Arc::new(SseState {
+            server: Arc::new(McpServer::new()),
+            sessions: Mutex::new(HashMap::new()),
+        });
+
+        let response = handle_message(
+            State(Arc::clone(&state)),
+            Query(HashMap::new()),
+            HeaderMap::new(),
+            r#"{"jsonrpc":"2.0","id":9,"method":"ping"}"#.to_string(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let mut query = HashMap::new();
+        query.insert(http_constants::SESSION_ID_QUERY.to_string(), "gone".to_string());
+        let response = handle_message(
+            State(state),
+            Query(query),
+            HeaderMap::new(),
+            r#"{"jsonrpc":"2.0","id":9,"method":"ping"}"#.to_string(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+}
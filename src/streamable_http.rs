@@ -0,0 +1,602 @@
+use crate::constants::{errors, http as http_constants, mcp as mcp_constants, mime_types};
+use crate::logging;
+use crate::mcp::*;
+use crate::server::{collect_batch, InFlightRequests, IncomingMessage, McpServer, Peer, SessionState};
+use crate::utils;
+use anyhow::Result;
+use axum::extract::State;
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use axum::{Json, Router};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::{ReceiverStream, UnboundedReceiverStream};
+use tokio_stream::StreamExt;
+
+/// State of one MCP session, created by `initialize` and addressed through
+/// the `Mcp-Session-Id` header.
+struct HttpSession {
+    in_flight: InFlightRequests,
+    peer: Peer,
+    state: SessionState,
+    // Taken by the first GET request that opens the server-initiated stream
+    notifications: Mutex<Option<mpsc::Receiver<String>>>,
+    last_seen: Mutex<Instant>,
+    // Whether the GET stream is open; such a session is not idle
+    streaming: AtomicBool,
+}
+
+impl HttpSession {
+    fn new() -> Self {
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let (buffer, buffered) = mpsc::channel(http_constants::SESSION_BUFFER_SIZE);
+
+        // Moves server-initiated messages into a bounded buffer, so a session
+        // whose GET stream is missing or stalled cannot pile them up
+        tokio::spawn(async move {
+            while let Some(message) = receiver.recv().await {
+                if buffer.try_send(message).is_err() {
+                    logging::log(
+                        LoggingLevel::Warning,
+                        mcp_constants::LOGGER_SERVER,
+                        serde_json::json!("Dropped a message for a session without a reading stream"),
+                    );
+                }
+            }
+        });
+
+        Self {
+            in_flight: InFlightRequests::new(),
+            peer: Peer::new(sender),
+            state: SessionState::new(),
+            notifications: Mutex::new(Some(buffered)),
+            last_seen: Mutex::new(Instant::now()),
+            streaming: AtomicBool::new(false),
+        }
+    }
+
+    fn touch(&self) {
+        *self.last_seen.lock().unwrap() = Instant::now();
+    }
+
+    fn is_idle(&self, idle: Duration) -> bool {
+        !self.streaming.load(Ordering::Relaxed) && self.last_seen.lock().unwrap().elapsed() >= idle
+    }
+
+    // Stops the session's work once it ended: its running requests are
+    // aborted and requests waiting for the client fail
+    fn close(&self) {
+        self.in_flight.cancel_all();
+        self.state.abandon_client_requests();
+    }
+}
+
+// Marks the session as streaming while the GET stream is open; the idle
+// timeout starts over when the stream is dropped
+struct StreamGuard {
+    session: Arc<HttpSession>,
+}
+
+impl StreamGuard {
+    fn new(session: Arc<HttpSession>) -> Self {
+        session.streaming.store(true, Ordering::Relaxed);
+        Self { session }
+    }
+}
+
+impl Drop for StreamGuard {
+    fn drop(&mut self) {
+        self.session.touch();
+        self.session.streaming.store(false, Ordering::Relaxed);
+    }
+}
+
+struct HttpState {
+    server: Arc<McpServer>,
+    sessions: Mutex<HashMap<String, Arc<HttpSession>>>,
+}
+
+impl HttpState {
+    fn create_session(&self) -> (String, Arc<HttpSession>) {
+        let session_id = uuid::Uuid::new_v4().to_string();
+        let session = Arc::new(HttpSession::new());
+        self.server.connect(&session.peer);
+        self.sessions
+            .lock()
+            .unwrap()
+            .insert(session_id.clone(), Arc::clone(&session));
+        (session_id, session)
+    }
+
+    // Looks up the session named by the request headers, answering 400 when
+    // the header is missing and 404 when the session is unknown or expired
+    #[allow(clippy::result_large_err)]
+    fn session(&self, headers: &HeaderMap) -> Result<(String, Arc<HttpSession>), Response> {
+        let Some(session_id) = headers
+            .get(http_constants::SESSION_ID_HEADER)
+            .and_then(|value| value.to_str().ok())
+        else {
+            return Err((StatusCode::BAD_REQUEST, errors::SESSION_ID_REQUIRED).into_response());
+        };
+
+        match self.sessions.lock().unwrap().get(session_id) {
+            Some(session) => {
+                session.touch();
+                Ok((session_id.to_string(), Arc::clone(session)))
+            }
+            None => Err((StatusCode::NOT_FOUND, errors::SESSION_NOT_FOUND).into_response()),
+        }
+    }
+
+    // Ends sessions that sent nothing for `idle` and hold no GET stream;
+    // their clients get 404 and start a new session
+    fn expire_idle_sessions(&self, idle: Duration) {
+        self.sessions.lock().unwrap().retain(|_, session| {
+            let idle = session.is_idle(idle);
+            if idle {
+                session.close();
+            }
+            !idle
+        });
+    }
+
+    fn remove_session(&self, session_id: &str) {
+        if let Some(session) = self.sessions.lock().unwrap().remove(session_id) {
+            session.close();
+        }
+    }
+}
+
+impl McpServer {
+    /// Serves the MCP Streamable HTTP transport on `listen`: clients POST
+    /// JSON-RPC messages to the MCP endpoint, GET it to receive
+    /// server-initiated messages over SSE and DELETE it to end their session.
+    pub async fn run_http(self, listen: SocketAddr) -> Result<()> {
+        let state = Arc::new(HttpState {
+            server: Arc::new(self),
+            sessions: Mutex::new(HashMap::new()),
+        });
+
+        let app = Router::new()
+            .route(
+                http_constants::MCP_ENDPOINT,
+                post(handle_post).get(handle_get).delete(handle_delete),
+            )
+            .with_state(Arc::clone(&state));
+
+        tokio::spawn(async move {
+            let mut sweep = tokio::time::interval(Duration::from_secs(http_constants::SESSION_SWEEP_INTERVAL_SECS));
+            loop {
+                sweep.tick().await;
+                state.expire_idle_sessions(Duration::from_secs(http_constants::SESSION_IDLE_TIMEOUT_SECS));
+            }
+        });
+
+        let listener = tokio::net::TcpListener::bind(listen).await?;
+        logging::log(
+            LoggingLevel::Info,
+            mcp_constants::LOGGER_SERVER,
+            serde_json::json!(format!("Listening on http://{}{}", listener.local_addr()?, http_constants::MCP_ENDPOINT)),
+        );
+        axum::serve(listener, app).await?;
+
+        Ok(())
+    }
+}
+
+async fn handle_post(State(state): State<Arc<HttpState>>, headers: HeaderMap, body: String) -> Response {
+    if !origin_allowed(&headers) {
+        return (StatusCode::FORBIDDEN, errors::ORIGIN_NOT_ALLOWED).into_response();
+    }
+    if !protocol_version_supported(&headers) {
+        return (StatusCode::BAD_REQUEST, errors::UNSUPPORTED_PROTOCOL_VERSION).into_response();
+    }
+
+    match McpServer::parse_message(&body) {
+        IncomingMessage::Request { id, request } => {
+            let (session_id, session) = if matches!(request, McpRequest::Initialize { .. }) {
+                state.create_session()
+            } else {
+                match state.session(&headers) {
+                    Ok(session) => session,
+                    Err(response) => return response,
+                }
+            };
+
+            // Every message for this request (progress, then the response)
+            // goes through its own channel, which closes when the task ends
+            let (sender, receiver) = mpsc::unbounded_channel();
+            let peer = Peer::new(sender);
+            let server = Arc::clone(&state.server);
+            let session_state = session.state.clone();
+            session.in_flight.spawn(id.clone(), async move {
+                let response = server.handle_request(request, &session_state, Some(peer.clone())).await;
+                peer.send_response(&response);
+            });
+
+            let mut response = if accepts_event_stream(&headers) {
+                event_stream(UnboundedReceiverStream::new(receiver)).into_response()
+            } else {
+                json_response(id, receiver, &session.peer).await
+            };
+            if let Ok(value) = HeaderValue::from_str(&session_id) {
+                response.headers_mut().insert(http_constants::SESSION_ID_HEADER, value);
+            }
+            response
+        }
+        IncomingMessage::Notification(notification) => {
+            let session = match state.session(&headers) {
+                Ok((_, session)) => session,
+                Err(response) => return response,
+            };
+            match notification {
+                McpNotification::Cancelled { params } => session.in_flight.cancel(&params.request_id),
+                notification => {
+                    state
+                        .server
+                        .handle_notification(notification, &session.state, Some(&session.peer))
+                        .await
+                }
+            }
+            StatusCode::ACCEPTED.into_response()
+        }
+        IncomingMessage::Invalid(response) => (StatusCode::BAD_REQUEST, Json(response)).into_response(),
+        IncomingMessage::Response { id, result } => match state.session(&headers) {
+            Ok((_, session)) => {
+                session.state.complete_request(id, result);
+                StatusCode::ACCEPTED.into_response()
+            }
+            Err(response) => response,
+        },
+        IncomingMessage::Ignored => StatusCode::ACCEPTED.into_response(),
+        // Batches are always answered with JSON; progress goes to the session's GET stream
+        IncomingMessage::Batch(messages) => {
+            let session = match state.session(&headers) {
+                Ok((_, session)) => session,
+                Err(response) => return response,
+            };
+            if let Some(error) = session.state.batch_error() {
+                return (StatusCode::BAD_REQUEST, Json(error)).into_response();
+            }
+            let responses = state
+                .server
+                .dispatch_batch(messages, &session.in_flight, &session.state, &session.peer)
+                .await;
+            match collect_batch(responses).await {
+                Some(response) => Json(response).into_response(),
+                None => StatusCode::ACCEPTED.into_response(),
+            }
+        }
+    }
+}
+
+async fn handle_get(State(state): State<Arc<HttpState>>, headers: HeaderMap) -> Response {
+    if !origin_allowed(&headers) {
+        return (StatusCode::FORBIDDEN, errors::ORIGIN_NOT_ALLOWED).into_response();
+    }
+    if !protocol_version_supported(&headers) {
+        return (StatusCode::BAD_REQUEST, errors::UNSUPPORTED_PROTOCOL_VERSION).into_response();
+    }
+    if !accepts_event_stream(&headers) {
+        return StatusCode::NOT_ACCEPTABLE.into_response();
+    }
+
+    let session = match state.session(&headers) {
+        Ok((_, session)) => session,
+        Err(response) => return response,
+    };
+
+    // Only one stream per session may carry server-initiated messages
+    let Some(receiver) = session.notifications.lock().unwrap().take() else {
+        return (StatusCode::CONFLICT, errors::STREAM_ALREADY_OPEN).into_response();
+    };
+    let guard = StreamGuard::new(session);
+    let messages = ReceiverStream::new(receiver).map(move |message| {
+        let _ = &guard;
+        message
+    });
+    event_stream(messages).into_response()
+}
+
+async fn handle_delete(State(state): State<Arc<HttpState>>, headers: HeaderMap) -> Response {
+    let session_id = match state.session(&headers) {
+        Ok((session_id, _)) => session_id,
+        Err(response) => return response,
+    };
+
+    state.remove_session(&session_id);
+    StatusCode::NO_CONTENT.into_response()
+}
+
+// Turns outgoing JSON-RPC messages into SSE `message` events
+pub(crate) fn message_events(
+    messages: impl tokio_stream::Stream<Item = String> + Send + 'static,
+) -> impl tokio_stream::Stream<Item = Result<Event, Infallible>> + Send + 'static {
+    messages.map(|message| Ok::<_, Infallible>(Event::default().event("message").data(message)))
+}
+
+fn event_stream(
+    messages: impl tokio_stream::Stream<Item = String> + Send + 'static,
+) -> Sse<impl tokio_stream::Stream<Item = Result<Event, Infallible>>> {
+    Sse::new(message_events(messages)).keep_alive(KeepAlive::default())
+}
+
+// Answers with the response to request `id`. Everything else the request
+// sends (progress, or requests to the client such as sampling) goes to the
+// session's GET stream as soon as it is produced, since the client may have
+// to act on it before the response can exist.
+async fn json_response(id: RequestId, mut receiver: mpsc::UnboundedReceiver<String>, session_peer: &Peer) -> Response {
+    while let Some(message) = receiver.recv().await {
+        if !is_response_to(&message, &id) {
+            session_peer.forward(message);
+            continue;
+        }
+        return (
+            [(header::CONTENT_TYPE, mime_types::APPLICATION_JSON)],
+            message,
+        )
+            .into_response();
+    }
+
+    // The request was cancelled before it produced a response
+    StatusCode::ACCEPTED.into_response()
+}
+
+// Responses carry the id of the request they answer and, unlike requests
+// sent to the client, no method
+fn is_response_to(message: &str, id: &RequestId) -> bool {
+    let Ok(message) = serde_json::from_str::<serde_json::Value>(message) else {
+        return false;
+    };
+    message.get("method").is_none()
+        && message
+            .get("id")
+            .and_then(|value| serde_json::from_value::<RequestId>(value.clone()).ok())
+            .is_some_and(|message_id| &message_id == id)
+}
+
+fn accepts_event_stream(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|accept| accept.contains(mime_types::TEXT_EVENT_STREAM))
+}
+
+// Clients send the negotiated revision on every request after initialization;
+// requests without the header are assumed to use the negotiated one
+fn protocol_version_supported(headers: &HeaderMap) -> bool {
+    headers
+        .get(http_constants::PROTOCOL_VERSION_HEADER)
+        .is_none_or(|value| value.to_str().ok().and_then(ProtocolVersion::parse).is_some())
+}
+
+// Guards against DNS rebinding: a browser Origin must point at the host that
+// was actually addressed
+pub(crate) fn origin_allowed(headers: &HeaderMap) -> bool {
+    let Some(origin) = headers.get(header::ORIGIN).and_then(|value| value.to_str().ok()) else {
+        return true;
+    };
+    let Some(host) = headers.get(header::HOST).and_then(|value| value.to_str().ok()) else {
+        return false;
+    };
+
+    utils::origin_authority(origin) == Some(host)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_state() -> Arc<HttpState> {
+        Arc::new(HttpState {
+            server: Arc::new(McpServer::new()),
+            sessions: Mutex::new(HashMap::new()),
+        })
+    }
+
+    fn session_headers(session_id: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(http_constants::SESSION_ID_HEADER, HeaderValue::from_str(session_id).unwrap());
+        headers
+    }
+
+    async fn body_json(response: Response) -> serde_json::Value {
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    const INITIALIZE: &str = r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{"protocolVersion":"2024-11-05","capabilities":{}}}"#;
+
+    #[tokio::test]
+    async fn test_initialize_creates_session() {
+        let state = create_state();
+
+        let response = handle_post(State(Arc::clone(&state)), HeaderMap::new(), INITIALIZE.to_string()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let session_id = response
+            .headers()
+            .get(http_constants::SESSION_ID_HEADER)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string();
+        assert!(state.sessions.lock().unwrap().contains_key(&session_id));
+
+        let body = body_json(response).await;
+        assert_eq!(body["id"], 1);
+        assert_eq!(body["result"]["serverInfo"]["name"], "get-my-notion-mcp");
+
+        let ping = r#"{"jsonrpc":"2.0","id":2,"method":"ping"}"#.to_string();
+        let response = handle_post(State(Arc::clone(&state)), session_headers(&session_id), ping).await;
+        assert_eq!(body_json(response).await["id"], 2);
+    }
+
+    #[tokio::test]
+    async fn test_requests_require_known_session() {
+        let state = create_state();
+        let ping = r#"{"jsonrpc":"2.0","id":2,"method":"ping"}"#;
+
+        let response = handle_post(State(Arc::clone(&state)), HeaderMap::new(), ping.to_string()).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = handle_post(State(Arc::clone(&state)), session_headers("unknown"), ping.to_string()).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_delete_ends_session() {
+        let state = create_state();
+        let (session_id, _) = state.create_session();
+
+        let response = handle_delete(State(Arc::clone(&state)), session_headers(&session_id)).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert!(state.sessions.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_idle_sessions_expire() {
+        let state = create_state();
+        let (session_id, _) = state.create_session();
+
+        state.expire_idle_sessions(Duration::from_secs(60));
+        assert!(state.sessions.lock().unwrap().contains_key(&session_id));
+
+        state.expire_idle_sessions(Duration::ZERO);
+        let ping = r#"{"jsonrpc":"2.0","id":2,"method":"ping"}"#;
+        let response = handle_post(State(state), session_headers(&session_id), ping.to_string()).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_ended_sessions_stop_their_work() {
+        let state = create_state();
+        let (session_id, session) = state.create_session();
+        let (running, stopped) = tokio::sync::oneshot::channel::<()>();
+        session.in_flight.spawn(RequestId::Number(1), async move {
+            let _running = running;
+            std::future::pending::<()>().await;
+        });
+        let waiting = tokio::spawn({
+            let session = Arc::clone(&session);
+            async move { session.state.send_request(&session.peer, ServerRequest::ListRoots).await }
+        });
+        tokio::task::yield_now().await;
+
+        let response = handle_delete(State(Arc::clone(&state)), session_headers(&session_id)).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert!(stopped.await.is_err());
+        let error = waiting.await.unwrap().unwrap_err();
+        assert_eq!(error.to_string(), errors::CLIENT_REQUEST_FAILED);
+    }
+
+    #[tokio::test]
+    async fn test_sessions_with_open_stream_do_not_expire() {
+        let state = create_state();
+        let (session_id, _) = state.create_session();
+        let mut headers = session_headers(&session_id);
+        headers.insert(header::ACCEPT, HeaderValue::from_static(mime_types::TEXT_EVENT_STREAM));
+
+        let stream = handle_get(State(Arc::clone(&state)), headers).await;
+        assert_eq!(stream.status(), StatusCode::OK);
+        state.expire_idle_sessions(Duration::ZERO);
+        assert!(state.sessions.lock().unwrap().contains_key(&session_id));
+
+        drop(stream);
+        state.expire_idle_sessions(Duration::ZERO);
+        assert!(state.sessions.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_unread_session_messages_are_bounded() {
+        let session = HttpSession::new();
+        for _ in 0..http_constants::SESSION_BUFFER_SIZE + 10 {
+            session.peer.forward("{}".to_string());
+        }
+        // Lets the forwarding task work through everything that was sent
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let mut receiver = session.notifications.lock().unwrap().take().unwrap();
+        let mut buffered = 0;
+        while receiver.try_recv().is_ok() {
+            buffered += 1;
+        }
+        assert_eq!(buffered, http_constants::SESSION_BUFFER_SIZE);
+    }
+
+    #[tokio::test]
+    async fn test_batch_is_answered_with_json_array() {
+        let state = create_state();
+        let (session_id, _) = state.create_session();
+        let batch = r#"[{"jsonrpc":"2.0","id":1,"method":"ping"},{"jsonrpc":"2.0","method":"notifications/initialized"},{"jsonrpc":"2.0","id":2,"method":"tools/list"}]"#;
+
+        let response = handle_post(State(Arc::clone(&state)), session_headers(&session_id), batch.to_string()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = body_json(response).await;
+        let mut ids: Vec<_> = body.as_array().unwrap().iter().map(|response| response["id"].clone()).collect();
+        ids.sort_by_key(|id| id.as_i64());
+        assert_eq!(ids, vec![serde_json::json!(1), serde_json::json!(2)]);
+
+        let notifications = r#"[{"jsonrpc":"2.0","method":"notifications/initialized"}]"#;
+        let response = handle_post(State(state), session_headers(&session_id), notifications.to_string()).await;
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+    }
+
+    #[tokio::test]
+    async fn test_json_response_forwards_client_requests_right_away() {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let (session_sender, mut session_receiver) = mpsc::unbounded_channel();
+        let session_peer = Peer::new(session_sender);
+        let answering = tokio::spawn(async move {
+            json_response(RequestId::Number(7), receiver, &session_peer).await
+        });
+
+        // The elicitation shares the id of the call but is a request, not its response
+        sender
+            .send(r#"{"jsonrpc":"2.0","id":7,"method":"elicitation/create","params":{}}"#.to_string())
+            .unwrap();
+        let forwarded = session_receiver.recv().await.unwrap();
+        assert!(forwarded.contains("elicitation/create"));
+
+        sender.send(r#"{"jsonrpc":"2.0","id":7,"result":{}}"#.to_string()).unwrap();
+        let response = answering.await.unwrap();
+        assert_eq!(body_json(response).await["id"], 7);
+    }
+
+    #[tokio::test]
+    async fn test_unsupported_protocol_version_header_is_rejected() {
+        let state = create_state();
+        let (session_id, _) = state.create_session();
+        let ping = r#"{"jsonrpc":"2.0","id":2,"method":"ping"}"#;
+
+        let mut headers = session_headers(&session_id);
+        headers.insert(http_constants::PROTOCOL_VERSION_HEADER, HeaderValue::from_static("2025-06-18"));
+        let response = handle_post(State(Arc::clone(&state)), headers.clone(), ping.to_string()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        headers.insert(http_constants::PROTOCOL_VERSION_HEADER, HeaderValue::from_static("1999-01-01"));
+        let response = handle_post(State(state), headers, ping.to_string()).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn test_origin_must_match_host() {
+        let mut headers = HeaderMap::new();
+        assert!(origin_allowed(&headers));
+
+        headers.insert(header::HOST, HeaderValue::from_static("127.0.0.1:8080"));
+        headers.insert(header::ORIGIN, HeaderValue::from_static("http://127.0.0.1:8080"));
+        assert!(origin_allowed(&headers));
+
+        headers.insert(header::ORIGIN, HeaderValue::from_static("https://evil.example"));
+        assert!(!origin_allowed(&headers));
+    }
+}
@@ -1,13 +1,136 @@
 use crate::constants::{github, mcp};
 use crate::mcp::*;
+use percent_encoding::{AsciiSet, NON_ALPHANUMERIC, utf8_percent_encode};
 
 // Common URL builders for GitHub API
 pub fn build_github_contents_url(owner: &str, repo: &str, path: &str) -> String {
-    format!("{}/repos/{}/{}/contents/{}", github::API_BASE_URL, owner, repo, path)
+    format!("{}/repos/{}/{}/contents/{}", github::API_BASE_URL, owner, repo, encode_path(path))
 }
 
 pub fn build_github_commits_url(owner: &str, repo: &str, branch: &str) -> String {
-    format!("{}/repos/{}/{}/commits/{}", github::API_BASE_URL, owner, repo, branch)
+    format!("{}/repos/{}/{}/commits/{}", github::API_BASE_URL, owner, repo, encode_path(branch))
+}
+
+pub fn build_github_commits_since_url(owner: &str, repo: &str, branch: &str, since: &str) -> String {
+    format!(
+        "{}/repos/{}/{}/commits?sha={}&since={}&per_page={}",
+        github::API_BASE_URL,
+        owner,
+        repo,
+        encode_url_component(branch),
+        encode_url_component(since),
+        github::COMMITS_PER_PAGE
+    )
+}
+
+pub fn build_github_tree_url(owner: &str, repo: &str, branch: &str) -> String {
+    format!("{}/repos/{}/{}/git/trees/{}?recursive=1", github::API_BASE_URL, owner, repo, encode_path(branch))
+}
+
+// Git Data API endpoints, e.g. `blobs`, `trees` or `refs/heads/main`
+pub fn build_github_git_url(owner: &str, repo: &str, path: &str) -> String {
+    format!("{}/repos/{}/{}/git/{}", github::API_BASE_URL, owner, repo, path)
+}
+
+pub fn build_github_pulls_url(owner: &str, repo: &str) -> String {
+    format!("{}/repos/{}/{}/pulls", github::API_BASE_URL, owner, repo)
+}
+
+pub fn build_github_branches_url(owner: &str, repo: &str) -> String {
+    format!("{}/repos/{}/{}/branches?per_page={}", github::API_BASE_URL, owner, repo, github::REFS_PER_PAGE)
+}
+
+pub fn build_github_tags_url(owner: &str, repo: &str) -> String {
+    format!("{}/repos/{}/{}/tags?per_page={}", github::API_BASE_URL, owner, repo, github::REFS_PER_PAGE)
+}
+
+// RFC 3986 unreserved characters are sent as they are, everything else is percent-encoded
+const URL_COMPONENT: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'.').remove(b'_').remove(b'~');
+
+// Percent-encodes a path segment or query parameter value, e.g. the `+` of a timezone offset
+pub fn encode_url_component(value: &str) -> String {
+    utf8_percent_encode(value, URL_COMPONENT).to_string()
+}
+
+// Percent-encodes each segment of a slash-separated path, keeping the slashes,
+// so names like `Meeting #3 ?.md` stay one path
+pub fn encode_path(path: &str) -> String {
+    path.split('/').map(encode_url_component).collect::<Vec<_>>().join("/")
+}
+
+// Appends the optional `ref` query parameter to a contents URL
+pub fn with_ref_query(url: String, git_ref: Option<&str>) -> String {
+    match git_ref {
+        Some(git_ref) if !git_ref.is_empty() => format!("{}?ref={}", url, encode_url_component(git_ref)),
+        _ => url,
+    }
+}
+
+// GitHub expects full ISO 8601 timestamps; accept plain YYYY-MM-DD dates as well.
+// Returns `None` unless `date` is a calendar date, optionally followed by
+// `THH:MM:SS`, fractional seconds and `Z` or a `±HH:MM` offset
+pub fn normalize_github_timestamp(date: &str) -> Option<String> {
+    let date = date.trim();
+    let (day, time) = match date.split_once('T') {
+        Some((day, time)) => (day, Some(time)),
+        None => (date, None),
+    };
+    if !is_calendar_date(day) {
+        return None;
+    }
+
+    match time {
+        None => Some(format!("{}T00:00:00Z", day)),
+        Some(time) if is_timestamp_time(time) => Some(date.to_string()),
+        Some(_) => None,
+    }
+}
+
+// Whether `value` has the shape of `pattern`, where `9` stands for any digit
+fn has_shape(value: &str, pattern: &str) -> bool {
+    value.len() == pattern.len()
+        && value
+            .bytes()
+            .zip(pattern.bytes())
+            .all(|(b, p)| if p == b'9' { b.is_ascii_digit() } else { b == p })
+}
+
+fn is_calendar_date(value: &str) -> bool {
+    if !has_shape(value, "9999-99-99") {
+        return false;
+    }
+
+    let number = |range: std::ops::Range<usize>| value[range].parse::<u32>().unwrap_or(0);
+    let (year, month, day) = (number(0..4), number(5..7), number(8..10));
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let days_in_month = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if leap => 29,
+        2 => 28,
+        _ => return false,
+    };
+    (1..=days_in_month).contains(&day)
+}
+
+// `HH:MM:SS`, optional fractional seconds, then `Z` or `±HH:MM`
+fn is_timestamp_time(value: &str) -> bool {
+    let Some(index) = value.find(['Z', '+', '-']) else {
+        return false;
+    };
+    let (clock, offset) = value.split_at(index);
+    let clock = match clock.split_once('.') {
+        Some((clock, fraction)) if !fraction.is_empty() && fraction.bytes().all(|b| b.is_ascii_digit()) => clock,
+        Some(_) => return false,
+        None => clock,
+    };
+
+    let number = |range: std::ops::Range<usize>| clock[range].parse::<u32>().unwrap_or(0);
+    has_shape(clock, "99:99:99")
+        && number(0..2) <= 23
+        && number(3..5) <= 59
+        && number(6..8) <= 59
+        && (offset == "Z" || has_shape(&offset[1..], "99:99"))
 }
 
 // Repository info formatter
@@ -45,6 +168,106 @@ pub fn format_file_content(path: &str, content: &str) -> String {
     format!("Content of file: {}\n\n```\n{}\n```", path, content)
 }
 
+// Commit list formatter for the weekly review prompt
+pub fn format_commit_list(since: &str, commits: &[crate::github::GitHubCommit]) -> String {
+    if commits.is_empty() {
+        return format!("No commits since {}.", since);
+    }
+
+    let mut content = format!("Commits since {}:\n\n", since);
+    for commit in commits {
+        let summary = commit.commit.message.lines().next().unwrap_or_default();
+        match &commit.commit.author {
+            Some(author) => content.push_str(&format!(
+                "- {} {} ({}, {})\n",
+                truncate_sha(&commit.sha),
+                summary,
+                author.name,
+                author.date
+            )),
+            None => content.push_str(&format!("- {} {}\n", truncate_sha(&commit.sha), summary)),
+        }
+    }
+
+    content
+}
+
+// Mondays that start the current and previous weeks (UTC) as YYYY-MM-DD,
+// newest first
+pub fn create_recent_week_starts(now: std::time::SystemTime, weeks: usize) -> Vec<String> {
+    let days = now
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |elapsed| (elapsed.as_secs() / 86_400) as i64);
+    // 1970-01-01 was a Thursday, three days after a Monday
+    let monday = days - (days + 3).rem_euclid(7);
+
+    (0..weeks as i64)
+        .map(|week| {
+            let (year, month, day) = civil_from_days(monday - 7 * week);
+            format!("{:04}-{:02}-{:02}", year, month, day)
+        })
+        .collect()
+}
+
+// Converts days since 1970-01-01 to a proleptic Gregorian date
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+// Orders completion candidates by how well they match the typed value:
+// path prefix, file name prefix, substring, then fuzzy subsequence match
+pub fn rank_completions<I>(candidates: I, value: &str) -> Vec<String>
+where
+    I: IntoIterator<Item = String>,
+{
+    let needle = value.to_lowercase();
+
+    let mut ranked: Vec<(u8, String)> = candidates
+        .into_iter()
+        .filter_map(|candidate| {
+            let haystack = candidate.to_lowercase();
+            let file_name = haystack.rsplit('/').find(|part| !part.is_empty()).unwrap_or("");
+
+            let score = if haystack.starts_with(&needle) {
+                0
+            } else if file_name.starts_with(&needle) {
+                1
+            } else if haystack.contains(&needle) {
+                2
+            } else if is_subsequence(&needle, &haystack) {
+                3
+            } else {
+                return None;
+            };
+
+            Some((score, candidate))
+        })
+        .collect();
+
+    ranked.sort_by(|(a_score, a), (b_score, b)| {
+        a_score
+            .cmp(b_score)
+            .then(a.len().cmp(&b.len()))
+            .then(a.cmp(b))
+    });
+
+    ranked.into_iter().map(|(_, candidate)| candidate).collect()
+}
+
+fn is_subsequence(needle: &str, haystack: &str) -> bool {
+    let mut haystack = haystack.chars();
+    needle.chars().all(|c| haystack.any(|h| h == c))
+}
+
 // SHA truncation utility
 pub fn truncate_sha(sha: &str) -> String {
     if sha.len() >= 7 {
@@ -55,31 +278,337 @@ pub fn truncate_sha(sha: &str) -> String {
 }
 
 // Tool creation helpers
+// Input schema derived from a tool's argument struct, without the meta-schema,
+// title and the struct's own doc comment
+pub fn create_input_schema<T: schemars::JsonSchema>() -> serde_json::Value {
+    let schema = schemars::generate::SchemaSettings::draft2020_12()
+        .with(|settings| settings.meta_schema = None)
+        .into_generator()
+        .into_root_schema_for::<T>();
+
+    let mut schema = serde_json::Value::from(schema);
+    if let Some(object) = schema.as_object_mut() {
+        object.remove("title");
+        object.remove("description");
+    }
+    schema
+}
+
+// Annotations of tools that only read from the GitHub repository
+pub fn create_read_only_annotations() -> ToolAnnotations {
+    ToolAnnotations {
+        read_only_hint: Some(true),
+        destructive_hint: Some(false),
+        idempotent_hint: Some(true),
+        open_world_hint: Some(true),
+    }
+}
+
+// Write tools change the repository and are not idempotent: repeating a
+// write fails once the SHA it was based on is gone
+pub fn create_write_annotations(destructive: bool) -> ToolAnnotations {
+    ToolAnnotations {
+        read_only_hint: Some(false),
+        destructive_hint: Some(destructive),
+        idempotent_hint: Some(false),
+        open_world_hint: Some(true),
+    }
+}
+
 pub fn create_list_files_tool() -> Tool {
     Tool {
         name: mcp::TOOL_LIST_FILES.to_string(),
+        title: Some("List Files".to_string()),
         description: "List files in the my-notion GitHub repository".to_string(),
-        input_schema: crate::constants::schemas::list_files_schema(),
+        input_schema: create_input_schema::<crate::handlers::ListFilesArguments>(),
+        output_schema: Some(crate::constants::schemas::list_files_output_schema()),
+        annotations: Some(create_read_only_annotations()),
     }
 }
 
 pub fn create_get_file_content_tool() -> Tool {
     Tool {
         name: mcp::TOOL_GET_FILE_CONTENT.to_string(),
+        title: Some("Get File Content".to_string()),
         description: "Get content of a specific file from the my-notion repository".to_string(),
-        input_schema: crate::constants::schemas::get_file_content_schema(),
+        input_schema: create_input_schema::<crate::handlers::GetFileContentArguments>(),
+        output_schema: Some(crate::constants::schemas::get_file_content_output_schema()),
+        annotations: Some(create_read_only_annotations()),
     }
 }
 
 pub fn create_get_latest_commit_tool() -> Tool {
     Tool {
         name: mcp::TOOL_GET_LATEST_COMMIT.to_string(),
+        title: Some("Get Latest Commit".to_string()),
         description: "Get the latest commit SHA from the my-notion repository".to_string(),
-        input_schema: crate::constants::schemas::get_latest_commit_schema(),
+        input_schema: create_input_schema::<crate::handlers::GetLatestCommitArguments>(),
+        output_schema: Some(crate::constants::schemas::get_latest_commit_output_schema()),
+        annotations: Some(create_read_only_annotations()),
+    }
+}
+
+pub fn create_summarize_note_tool() -> Tool {
+    Tool {
+        name: mcp::TOOL_SUMMARIZE_NOTE.to_string(),
+        title: Some("Summarize Note".to_string()),
+        description: "Summarize a note from the my-notion repository with the client's model".to_string(),
+        input_schema: create_input_schema::<crate::handlers::SummarizeNoteArguments>(),
+        output_schema: Some(crate::constants::schemas::summarize_note_output_schema()),
+        annotations: Some(create_read_only_annotations()),
+    }
+}
+
+pub fn create_create_or_update_file_tool() -> Tool {
+    Tool {
+        name: mcp::TOOL_CREATE_OR_UPDATE_FILE.to_string(),
+        title: Some("Create or Update File".to_string()),
+        description: "Create a file in the my-notion repository or replace its content. Commits to a notes/<topic> branch with a pull request, or to main when the server allows direct writes".to_string(),
+        input_schema: create_input_schema::<crate::handlers::CreateOrUpdateFileArguments>(),
+        output_schema: Some(crate::constants::schemas::write_file_output_schema()),
+        annotations: Some(create_write_annotations(true)),
+    }
+}
+
+pub fn create_delete_file_tool() -> Tool {
+    Tool {
+        name: mcp::TOOL_DELETE_FILE.to_string(),
+        title: Some("Delete File".to_string()),
+        description: "Delete a file from the my-notion repository. Commits to a notes/<topic> branch with a pull request, or to main when the server allows direct writes".to_string(),
+        input_schema: create_input_schema::<crate::handlers::DeleteFileArguments>(),
+        output_schema: Some(crate::constants::schemas::write_file_output_schema()),
+        annotations: Some(create_write_annotations(true)),
+    }
+}
+
+pub fn create_commit_changes_tool() -> Tool {
+    Tool {
+        name: mcp::TOOL_COMMIT_CHANGES.to_string(),
+        title: Some("Commit Changes".to_string()),
+        description: "Write and delete several files of the my-notion repository in a single commit, on a notes/<topic> branch with a pull request or on main when the server allows direct writes".to_string(),
+        input_schema: create_input_schema::<crate::handlers::CommitChangesArguments>(),
+        output_schema: Some(crate::constants::schemas::commit_changes_output_schema()),
+        annotations: Some(create_write_annotations(true)),
+    }
+}
+
+pub fn create_create_branch_tool() -> Tool {
+    Tool {
+        name: mcp::TOOL_CREATE_BRANCH.to_string(),
+        title: Some("Create Branch".to_string()),
+        description: "Create a branch in the my-notion repository, by default notes/<topic> from main".to_string(),
+        input_schema: create_input_schema::<crate::handlers::CreateBranchArguments>(),
+        output_schema: Some(crate::constants::schemas::create_branch_output_schema()),
+        annotations: Some(create_write_annotations(false)),
+    }
+}
+
+pub fn create_open_pull_request_tool() -> Tool {
+    Tool {
+        name: mcp::TOOL_OPEN_PULL_REQUEST.to_string(),
+        title: Some("Open Pull Request".to_string()),
+        description: "Open a pull request from a branch into main, or return the one already open".to_string(),
+        input_schema: create_input_schema::<crate::handlers::OpenPullRequestArguments>(),
+        output_schema: Some(crate::constants::schemas::pull_request_output_schema()),
+        // Calling it again returns the pull request that is already open
+        annotations: Some(ToolAnnotations {
+            idempotent_hint: Some(true),
+            ..create_write_annotations(false)
+        }),
+    }
+}
+
+// Tool result carrying both the human-readable text and typed JSON
+pub fn create_structured_tool_result(text: String, structured_content: serde_json::Value) -> CallToolResult {
+    CallToolResult {
+        content: vec![ToolContent::Text { text }],
+        structured_content: Some(structured_content),
+        is_error: None,
+    }
+}
+
+// Failed tool execution, reported in the result so the model can react to it
+pub fn create_tool_error_result(message: &str) -> CallToolResult {
+    CallToolResult {
+        content: vec![ToolContent::Text {
+            text: message.to_string(),
+        }],
+        structured_content: None,
+        is_error: Some(true),
+    }
+}
+
+// Structured outputs matching the tools' output schemas
+pub fn create_file_list_output(files: &[crate::github::GitHubFile], next_cursor: Option<&str>) -> serde_json::Value {
+    let mut output = serde_json::json!({ "files": files });
+    if let Some(next_cursor) = next_cursor {
+        output["nextCursor"] = serde_json::Value::String(next_cursor.to_string());
+    }
+    output
+}
+
+pub fn create_file_content_output(
+    file: &crate::github::GitHubContent,
+    git_ref: Option<&str>,
+    content: &str,
+) -> serde_json::Value {
+    serde_json::json!({
+        "path": file.path,
+        "ref": git_ref,
+        "sha": file.sha,
+        "size": file.size,
+        "content": content,
+    })
+}
+
+pub fn create_commit_output(commit: &crate::github::GitHubCommit) -> serde_json::Value {
+    let author = commit.commit.author.as_ref();
+    serde_json::json!({
+        "sha": commit.sha,
+        "message": commit.commit.message,
+        "author": author.map(|author| &author.name),
+        "date": author.map(|author| &author.date),
+        "url": commit.html_url,
+    })
+}
+
+pub fn create_summary_output(path: &str, git_ref: Option<&str>, summary: &str, model: &str) -> serde_json::Value {
+    serde_json::json!({
+        "path": path,
+        "ref": git_ref,
+        "summary": summary,
+        "model": model,
+    })
+}
+
+pub fn create_write_file_output(
+    path: &str,
+    branch: &str,
+    update: &crate::github::GitHubContentUpdate,
+    pull_request: Option<&crate::github::GitHubPullRequest>,
+) -> serde_json::Value {
+    serde_json::json!({
+        "path": path,
+        "branch": branch,
+        "sha": update.content.as_ref().map(|file| &file.sha),
+        "commitSha": update.commit.sha,
+        "commitUrl": update.commit.html_url,
+        "pullRequestNumber": pull_request.map(|pull_request| pull_request.number),
+        "pullRequestUrl": pull_request.map(|pull_request| &pull_request.html_url),
+    })
+}
+
+pub fn create_branch_output(branch: &str, sha: &str) -> serde_json::Value {
+    serde_json::json!({
+        "branch": branch,
+        "sha": sha,
+    })
+}
+
+pub fn create_pull_request_output(branch: &str, pull_request: &crate::github::GitHubPullRequest) -> serde_json::Value {
+    serde_json::json!({
+        "branch": branch,
+        "title": pull_request.title,
+        "pullRequestNumber": pull_request.number,
+        "pullRequestUrl": pull_request.html_url,
+    })
+}
+
+// Appends the review pull request, if any, to a write tool's text
+pub fn format_write_result(text: &str, pull_request: Option<&crate::github::GitHubPullRequest>) -> String {
+    match pull_request {
+        Some(pull_request) => format!("{}\nPull request #{}: {}", text, pull_request.number, pull_request.html_url),
+        None => text.to_string(),
+    }
+}
+
+// Branch for a topic: `notes/` and the topic lowercased, with every run of
+// other characters than letters and digits replaced by one dash
+pub fn create_topic_branch_name(topic: &str) -> Option<String> {
+    let slug = topic
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+    (!slug.is_empty()).then(|| format!("{}{}", github::TOPIC_BRANCH_PREFIX, slug))
+}
+
+pub fn create_commit_changes_output(
+    branch: &str,
+    base_sha: &str,
+    plan: &[crate::handlers::PlannedChange],
+    dry_run: bool,
+    commit: Option<&crate::github::GitHubWriteCommit>,
+    pull_request: Option<&crate::github::GitHubPullRequest>,
+) -> serde_json::Value {
+    serde_json::json!({
+        "branch": branch,
+        "baseSha": base_sha,
+        "dryRun": dry_run,
+        "changes": plan,
+        "commitSha": commit.map(|commit| &commit.sha),
+        "commitUrl": commit.and_then(|commit| commit.html_url.as_ref()),
+        "pullRequestNumber": pull_request.map(|pull_request| pull_request.number),
+        "pullRequestUrl": pull_request.map(|pull_request| &pull_request.html_url),
+    })
+}
+
+// One line per file, marked like `git status --short`
+pub fn format_planned_changes(
+    branch: &str,
+    base_sha: &str,
+    plan: &[crate::handlers::PlannedChange],
+    commit: Option<&crate::github::GitHubWriteCommit>,
+) -> String {
+    let header = match commit {
+        Some(commit) => format!("Committed to {} in {} (parent {}):", branch, commit.sha, base_sha),
+        None => format!("Planned changes to {} at {} (not committed):", branch, base_sha),
+    };
+    let lines: Vec<String> = plan
+        .iter()
+        .map(|change| format!("{} {}", change.action.marker(), change.path))
+        .collect();
+    format!("{}\n{}", header, lines.join("\n"))
+}
+
+// Sampling request asking the client's model to summarize a note
+pub fn create_summary_request(path: &str, content: &str) -> CreateMessageParams {
+    CreateMessageParams {
+        messages: vec![SamplingMessage {
+            role: Role::User,
+            content: SamplingContent::Text {
+                text: format_file_content(path, content),
+            },
+        }],
+        system_prompt: Some(
+            "Summarize the note you are given from the my-notion repository. \
+             Highlight the key points, decisions and open questions."
+                .to_string(),
+        ),
+        max_tokens: mcp::SUMMARY_MAX_TOKENS,
     }
 }
 
-// Resource creation helper
+// Elicitation asking the user which of `choices` they meant by a missing path
+pub fn create_path_choice_request(path: &str, choices: &[String]) -> ElicitParams {
+    ElicitParams {
+        message: format!("{} does not exist. Which note did you mean?", path),
+        requested_schema: serde_json::json!({
+            "type": "object",
+            "properties": {
+                (mcp::PARAM_PATH): {
+                    "type": "string",
+                    "title": "Note",
+                    "enum": choices,
+                }
+            },
+            "required": [mcp::PARAM_PATH],
+        }),
+    }
+}
+
+// Resource creation helpers
 pub fn create_repo_info_resource() -> Resource {
     Resource {
         uri: mcp::RESOURCE_REPO_INFO.to_string(),
@@ -89,16 +618,132 @@ pub fn create_repo_info_resource() -> Resource {
     }
 }
 
+pub fn create_file_resource_template() -> ResourceTemplate {
+    ResourceTemplate {
+        uri_template: mcp::RESOURCE_FILE_TEMPLATE.to_string(),
+        name: "My Notion File".to_string(),
+        description: Some("Content of a file in the my-notion repository".to_string()),
+        mime_type: Some(crate::constants::mime_types::TEXT_PLAIN.to_string()),
+    }
+}
+
+// Concrete resource for one note, e.g. a pinned note registered at runtime
+pub fn create_note_file_resource(path: &str) -> Resource {
+    Resource {
+        uri: format!("{}{}", mcp::RESOURCE_FILE_PREFIX, path),
+        name: path.to_string(),
+        description: Some(format!("Note {} in the my-notion repository", path)),
+        mime_type: Some(crate::constants::mime_types::TEXT_PLAIN.to_string()),
+    }
+}
+
+// Prompt creation helpers
+pub fn create_summarize_note_prompt() -> Prompt {
+    Prompt {
+        name: mcp::PROMPT_SUMMARIZE_NOTE.to_string(),
+        description: Some("Summarize a note from the my-notion repository".to_string()),
+        arguments: Some(vec![PromptArgument {
+            name: mcp::PARAM_PATH.to_string(),
+            description: Some("Path to the note within the repository".to_string()),
+            required: Some(true),
+        }]),
+    }
+}
+
+pub fn create_weekly_review_prompt() -> Prompt {
+    Prompt {
+        name: mcp::PROMPT_WEEKLY_REVIEW.to_string(),
+        description: Some("Review changes made to the my-notion repository since a date".to_string()),
+        arguments: Some(vec![PromptArgument {
+            name: mcp::PARAM_DATE.to_string(),
+            description: Some("Start date of the review (YYYY-MM-DD)".to_string()),
+            required: Some(true),
+        }]),
+    }
+}
+
+pub fn create_find_related_notes_prompt() -> Prompt {
+    Prompt {
+        name: mcp::PROMPT_FIND_RELATED_NOTES.to_string(),
+        description: Some("Find notes in the my-notion repository related to a topic".to_string()),
+        arguments: Some(vec![PromptArgument {
+            name: mcp::PARAM_TOPIC.to_string(),
+            description: Some("Topic to look for".to_string()),
+            required: Some(true),
+        }]),
+    }
+}
+
+// Single user-message prompt result
+pub fn create_user_prompt_result(description: &str, text: String) -> GetPromptResult {
+    GetPromptResult {
+        description: Some(description.to_string()),
+        messages: vec![PromptMessage {
+            role: Role::User,
+            content: PromptContent::Text { text },
+        }],
+    }
+}
+
+// Cursor pagination. Cursors are opaque to clients and encode the offset of
+// the page they start; a cursor past the end of `items` is rejected.
+pub fn paginate<T>(
+    items: Vec<T>,
+    cursor: Option<&str>,
+    page_size: usize,
+) -> Result<(Vec<T>, Option<String>), crate::error::ToolError> {
+    let offset = match cursor {
+        Some(cursor) => parse_cursor(cursor)
+            .filter(|offset| *offset < items.len())
+            .ok_or_else(|| crate::error::ToolError::invalid_argument(mcp::PARAM_CURSOR, crate::constants::errors::INVALID_CURSOR))?,
+        None => 0,
+    };
+
+    let end = offset.saturating_add(page_size.max(1)).min(items.len());
+    let next_cursor = (end < items.len()).then(|| create_cursor(end));
+    Ok((items.into_iter().skip(offset).take(end - offset).collect(), next_cursor))
+}
+
+fn create_cursor(offset: usize) -> String {
+    use base64::Engine;
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(offset.to_string())
+}
+
+fn parse_cursor(cursor: &str) -> Option<usize> {
+    use base64::Engine;
+    let decoded = base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(cursor).ok()?;
+    String::from_utf8(decoded).ok()?.parse().ok()
+}
+
 // Server capabilities creation
-pub fn create_server_capabilities() -> ServerCapabilities {
+pub fn create_server_capabilities(protocol_version: ProtocolVersion) -> ServerCapabilities {
     ServerCapabilities {
         tools: Some(ToolsCapability {
-            list_changed: Some(false),
+            list_changed: Some(true),
         }),
         resources: Some(ResourcesCapability {
             subscribe: Some(false),
+            list_changed: Some(true),
+        }),
+        prompts: Some(PromptsCapability {
             list_changed: Some(false),
         }),
+        completions: protocol_version
+            .supports_completions()
+            .then_some(CompletionsCapability {}),
+        logging: Some(LoggingCapability {}),
+    }
+}
+
+// Completion result creation, capped at the protocol limit of values per response
+pub fn create_completion(mut values: Vec<String>) -> Completion {
+    let total = values.len();
+    values.truncate(mcp::MAX_COMPLETION_VALUES);
+
+    Completion {
+        has_more: Some(total > values.len()),
+        total: Some(total as u32),
+        values,
     }
 }
 
@@ -107,7 +752,44 @@ pub fn create_server_info() -> ServerInfo {
     ServerInfo {
         name: mcp::SERVER_NAME.to_string(),
         version: mcp::SERVER_VERSION.to_string(),
+        title: Some(mcp::SERVER_TITLE.to_string()),
+        website_url: Some(mcp::SERVER_WEBSITE_URL.to_string()),
+    }
+}
+
+// Usage guide sent as `instructions` during initialize. `top_level` is the
+// repository root listing; the layout summary is left out when it is empty.
+pub fn create_server_instructions(owner: &str, repo: &str, top_level: &[crate::github::GitHubFile]) -> String {
+    let mut instructions = format!(
+        "This server reads notes from the GitHub repository {}/{} (branch {}).\n",
+        owner,
+        repo,
+        github::DEFAULT_BRANCH
+    );
+
+    if !top_level.is_empty() {
+        let entries: Vec<String> = top_level
+            .iter()
+            .map(|file| match file.file_type.as_str() {
+                "dir" => format!("{}/", file.name),
+                _ => file.name.clone(),
+            })
+            .collect();
+        instructions.push_str(&format!("Top-level entries: {}.\n", entries.join(", ")));
     }
+
+    instructions.push_str(
+        "Notes are Notion Markdown exports: each page is a .md file whose name ends with the page ID, \
+         sub-pages live in a folder named like their parent page, and attachments sit next to the page that embeds them.\n",
+    );
+    instructions.push_str(&format!(
+        "Recommended order: {} to find notes (recursive for a full index), {} to read one, and {} to check whether anything changed since your last read.",
+        mcp::TOOL_LIST_FILES,
+        mcp::TOOL_GET_FILE_CONTENT,
+        mcp::TOOL_GET_LATEST_COMMIT
+    ));
+
+    instructions
 }
 
 // Common error creation helpers
@@ -117,12 +799,48 @@ pub fn create_method_not_found_error(id: RequestId, method_name: &str) -> McpRes
         id,
         error: McpError {
             code: crate::constants::rpc_errors::METHOD_NOT_FOUND,
-            message: format!("{}: '{}'", crate::constants::errors::TOOL_NOT_FOUND, method_name),
+            message: format!("{}: '{}'", crate::constants::errors::METHOD_NOT_FOUND, method_name),
+            data: None,
+        },
+    }
+}
+
+pub fn create_invalid_request_error(id: RequestId, message: &str) -> McpResponse {
+    McpResponse::Error {
+        jsonrpc: "2.0".to_string(),
+        id,
+        error: McpError {
+            code: crate::constants::rpc_errors::INVALID_REQUEST,
+            message: message.to_string(),
             data: None,
         },
     }
 }
 
+pub fn create_invalid_params_error(id: RequestId, message: &str) -> McpResponse {
+    McpResponse::Error {
+        jsonrpc: "2.0".to_string(),
+        id,
+        error: McpError {
+            code: crate::constants::rpc_errors::INVALID_PARAMS,
+            message: message.to_string(),
+            data: None,
+        },
+    }
+}
+
+pub fn create_resource_not_found_error(id: RequestId, uri: &str) -> McpResponse {
+    McpResponse::Error {
+        jsonrpc: "2.0".to_string(),
+        id,
+        error: McpError {
+            code: crate::constants::rpc_errors::RESOURCE_NOT_FOUND,
+            message: format!("{}: '{}'", crate::constants::errors::RESOURCE_NOT_FOUND, uri),
+            data: Some(serde_json::json!({ "uri": uri })),
+        },
+    }
+}
+
 pub fn create_internal_error(id: RequestId, message: &str) -> McpResponse {
     McpResponse::Error {
         jsonrpc: "2.0".to_string(),
@@ -147,6 +865,13 @@ pub fn create_parse_error(id: RequestId, message: &str) -> McpResponse {
     }
 }
 
+// Extracts `host[:port]` from an Origin header value such as `http://localhost:8080`
+pub fn origin_authority(origin: &str) -> Option<&str> {
+    origin
+        .split_once("://")
+        .map(|(_, rest)| rest.split('/').next().unwrap_or(rest))
+}
+
 // HTTP request header helper
 pub fn get_user_agent_header() -> (&'static str, &'static str) {
     ("User-Agent", github::USER_AGENT)
@@ -1,15 +1,18 @@
 use get_my_notion_mcp::mcp::*;
 use get_my_notion_mcp::server::McpServer;
 use get_my_notion_mcp::handlers::*;
+#[allow(clippy::single_component_path_imports)]
 use serde_json;
 use std::collections::HashMap;
 
+#[allow(clippy::assertions_on_constants)]
 #[tokio::test]
 async fn test_mcp_server_initialization() {
     let mut server = McpServer::new();
 
     let list_files_tool = Tool {
         name: "list_files".to_string(),
+        title: None,
         description: "List files in the my-notion GitHub repository".to_string(),
         input_schema: serde_json::json!({
             "type": "object",
@@ -20,6 +23,8 @@ async fn test_mcp_server_initialization() {
                 }
             }
         }),
+        output_schema: None,
+        annotations: None,
     };
 
     let repo_info_resource = Resource {
@@ -47,6 +52,7 @@ fn test_mcp_request_serialization() {
                     list_changed: Some(false),
                 }),
                 sampling: Some(SamplingCapability {}),
+                elicitation: None,
             },
             client_info: Some(ClientInfo {
                 name: "test-client".to_string(),
@@ -75,11 +81,19 @@ fn test_mcp_response_serialization() {
                     subscribe: Some(false),
                     list_changed: Some(false),
                 }),
+                prompts: Some(PromptsCapability {
+                    list_changed: Some(false),
+                }),
+                completions: Some(CompletionsCapability {}),
+                logging: None,
             },
             server_info: ServerInfo {
                 name: "get-my-notion-mcp".to_string(),
                 version: "0.1.5".to_string(),
+                title: None,
+                website_url: None,
             },
+            instructions: None,
         },
     };
 
@@ -133,6 +147,7 @@ fn test_call_tool_params() {
     let params = CallToolParams {
         name: "get_file_content".to_string(),
         arguments: Some(arguments),
+        meta: None,
     };
 
     let serialized = serde_json::to_string(&params).unwrap();
@@ -148,4 +163,593 @@ fn test_read_resource_params() {
 
     let serialized = serde_json::to_string(&params).unwrap();
     assert!(serialized.contains("notion://repo/info"));
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_get_prompt_request_deserialization() {
+    let request: McpRequest = serde_json::from_str(
+        r#"{"jsonrpc":"2.0","id":3,"method":"prompts/get","params":{"name":"summarize_note","arguments":{"path":"README.md"}}}"#,
+    )
+    .unwrap();
+
+    match request {
+        McpRequest::GetPrompt { params, .. } => {
+            assert_eq!(params.name, "summarize_note");
+            assert_eq!(params.arguments.unwrap()["path"], "README.md");
+        }
+        other => panic!("unexpected request: {:?}", other),
+    }
+}
+
+#[test]
+fn test_get_prompt_result_serialization() {
+    let result = GetPromptResult {
+        description: Some("Summary of README.md".to_string()),
+        messages: vec![PromptMessage {
+            role: Role::User,
+            content: PromptContent::Text {
+                text: "Summarize this note".to_string(),
+            },
+        }],
+    };
+
+    let serialized = serde_json::to_value(&result).unwrap();
+    assert_eq!(serialized["messages"][0]["role"], "user");
+    assert_eq!(serialized["messages"][0]["content"]["type"], "text");
+}
+
+#[test]
+fn test_complete_request_deserialization() {
+    let request: McpRequest = serde_json::from_str(
+        r#"{"jsonrpc":"2.0","id":4,"method":"completion/complete","params":{"ref":{"type":"ref/resource","uri":"notion://file/{path}"},"argument":{"name":"path","value":"meet"}}}"#,
+    )
+    .unwrap();
+
+    match request {
+        McpRequest::Complete { params, .. } => {
+            assert!(matches!(params.reference, CompletionReference::Resource { ref uri } if uri == "notion://file/{path}"));
+            assert_eq!(params.argument.name, "path");
+            assert_eq!(params.argument.value, "meet");
+        }
+        other => panic!("unexpected request: {:?}", other),
+    }
+}
+
+#[test]
+fn test_completion_ranking_and_capping() {
+    let candidates = vec![
+        "archive/meeting-notes.md".to_string(),
+        "meetings/weekly.md".to_string(),
+        "notes/team-meeting.md".to_string(),
+        "misc/mixed-entries.md".to_string(),
+        "notes/todo.md".to_string(),
+    ];
+
+    let ranked = get_my_notion_mcp::utils::rank_completions(candidates, "meet");
+    assert_eq!(ranked[0], "meetings/weekly.md");
+    assert_eq!(ranked[1], "archive/meeting-notes.md");
+    assert_eq!(ranked[2], "notes/team-meeting.md");
+    assert!(!ranked.contains(&"notes/todo.md".to_string()));
+
+    let values = (0..150).map(|i| format!("note-{}.md", i)).collect();
+    let completion = get_my_notion_mcp::utils::create_completion(values);
+    assert_eq!(completion.values.len(), 100);
+    assert_eq!(completion.total, Some(150));
+    assert_eq!(completion.has_more, Some(true));
+}
+
+#[test]
+fn test_recent_week_starts_are_mondays() {
+    // Wednesday 2025-01-08
+    let now = std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_736_294_400);
+
+    let weeks = get_my_notion_mcp::utils::create_recent_week_starts(now, 3);
+    assert_eq!(weeks, vec!["2025-01-06", "2024-12-30", "2024-12-23"]);
+}
+
+#[test]
+fn test_review_dates_are_validated_and_encoded() {
+    use get_my_notion_mcp::utils::{build_github_commits_since_url, normalize_github_timestamp};
+
+    assert_eq!(normalize_github_timestamp(" 2024-02-29 ").as_deref(), Some("2024-02-29T00:00:00Z"));
+    assert_eq!(
+        normalize_github_timestamp("2025-06-01T00:00:00.5+09:00").as_deref(),
+        Some("2025-06-01T00:00:00.5+09:00")
+    );
+    for invalid in ["2025-02-29", "2025/06/01", "tomorrow!!", "2025-06-01T00:00", "2025-06-01T00:00:00+9"] {
+        assert_eq!(normalize_github_timestamp(invalid), None, "{}", invalid);
+    }
+
+    let url = build_github_commits_since_url("owner", "repo", "main", "2025-06-01T00:00:00+09:00");
+    assert!(url.ends_with("?sha=main&since=2025-06-01T00%3A00%3A00%2B09%3A00&per_page=100"), "{}", url);
+}
+
+#[test]
+fn test_branch_names_are_encoded_in_urls() {
+    use get_my_notion_mcp::utils::{build_github_commits_url, build_github_tree_url};
+
+    let url = build_github_commits_url("owner", "repo", "notes/a b#1?");
+    assert!(url.ends_with("/repos/owner/repo/commits/notes/a%20b%231%3F"), "{}", url);
+
+    let url = build_github_tree_url("owner", "repo", "notes/a b#1?");
+    assert!(url.ends_with("/repos/owner/repo/git/trees/notes/a%20b%231%3F?recursive=1"), "{}", url);
+}
+
+fn error_code(response: &McpResponse) -> i32 {
+    match response {
+        McpResponse::Error { error, .. } => error.code,
+        other => panic!("expected an error response, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_notifications_never_get_responses() {
+    let server = McpServer::new();
+
+    for message in [
+        r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#,
+        r#"{"jsonrpc":"2.0","method":"initialized"}"#,
+        r#"{"jsonrpc":"2.0","method":"notifications/cancelled","params":{"requestId":7,"reason":"user"}}"#,
+        r#"{"jsonrpc":"2.0","method":"notifications/unknown"}"#,
+    ] {
+        assert!(server.handle_message(message).await.is_none(), "{}", message);
+    }
+}
+
+#[tokio::test]
+async fn test_ping_returns_empty_result() {
+    let server = McpServer::new();
+
+    let response = server
+        .handle_message(r#"{"jsonrpc":"2.0","id":1,"method":"ping"}"#)
+        .await
+        .unwrap();
+
+    let serialized = serde_json::to_value(&response).unwrap();
+    assert_eq!(serialized["id"], 1);
+    assert_eq!(serialized["result"], serde_json::json!({}));
+}
+
+#[tokio::test]
+async fn test_dispatch_error_codes() {
+    let server = McpServer::new();
+
+    let response = server.handle_message("{not json").await.unwrap();
+    assert_eq!(error_code(&response), -32700);
+
+    let response = server
+        .handle_message(r#"{"jsonrpc":"2.0","id":2,"method":"sampling/unknown"}"#)
+        .await
+        .unwrap();
+    assert_eq!(error_code(&response), -32601);
+
+    let response = server
+        .handle_message(r#"{"jsonrpc":"2.0","id":3,"method":"tools/call","params":{"arguments":{}}}"#)
+        .await
+        .unwrap();
+    assert_eq!(error_code(&response), -32602);
+
+    let response = server
+        .handle_message(r#"{"jsonrpc":"2.0","id":4,"method":"tools/call","params":{"name":"missing"}}"#)
+        .await
+        .unwrap();
+    assert_eq!(error_code(&response), -32602);
+
+    let response = server
+        .handle_message(r#"{"jsonrpc":"2.0","id":5,"method":"resources/read","params":{"uri":"notion://missing"}}"#)
+        .await
+        .unwrap();
+    assert_eq!(error_code(&response), -32002);
+
+    let response = server.handle_message(r#"{"jsonrpc":"2.0","id":6}"#).await.unwrap();
+    assert_eq!(error_code(&response), -32600);
+}
+
+#[test]
+fn test_call_tool_params_progress_token() {
+    let params: CallToolParams = serde_json::from_str(
+        r#"{"name":"list_files","arguments":{"recursive":true},"_meta":{"progressToken":42}}"#,
+    )
+    .unwrap();
+
+    assert_eq!(params.meta.unwrap().progress_token, Some(ProgressToken::Number(42)));
+}
+
+#[test]
+fn test_protocol_version_negotiation() {
+    assert_eq!(ProtocolVersion::negotiate("2024-11-05"), ProtocolVersion::V2024_11_05);
+    assert_eq!(ProtocolVersion::negotiate("2025-03-26"), ProtocolVersion::V2025_03_26);
+    assert_eq!(ProtocolVersion::negotiate("2025-06-18"), ProtocolVersion::V2025_06_18);
+    // Unknown revisions fall back to the newest one not newer than requested
+    assert_eq!(ProtocolVersion::negotiate("2025-05-01"), ProtocolVersion::V2025_03_26);
+    assert_eq!(ProtocolVersion::negotiate("2026-01-01"), ProtocolVersion::LATEST);
+    assert_eq!(ProtocolVersion::negotiate("2024-01-01"), ProtocolVersion::LATEST);
+}
+
+#[tokio::test]
+async fn test_initialize_adapts_to_negotiated_version() {
+    let server = McpServer::new();
+
+    let response = server
+        .handle_message(r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{"protocolVersion":"2024-11-05","capabilities":{}}}"#)
+        .await
+        .unwrap();
+    let serialized = serde_json::to_value(&response).unwrap();
+    assert_eq!(serialized["result"]["protocolVersion"], "2024-11-05");
+    assert!(serialized["result"]["capabilities"].get("completions").is_none());
+
+    let response = server
+        .handle_message(r#"{"jsonrpc":"2.0","id":2,"method":"initialize","params":{"protocolVersion":"2025-06-18","capabilities":{}}}"#)
+        .await
+        .unwrap();
+    let serialized = serde_json::to_value(&response).unwrap();
+    assert_eq!(serialized["result"]["protocolVersion"], "2025-06-18");
+    assert_eq!(serialized["result"]["capabilities"]["completions"], serde_json::json!({}));
+}
+
+#[tokio::test]
+async fn test_batch_responses_omit_notifications() {
+    let server = McpServer::new();
+
+    let response = server
+        .handle_message(
+            r#"[{"jsonrpc":"2.0","id":1,"method":"ping"},{"jsonrpc":"2.0","method":"notifications/initialized"},{"jsonrpc":"2.0","id":2,"method":"unknown/method"},42]"#,
+        )
+        .await
+        .unwrap();
+    let serialized = serde_json::to_value(&response).unwrap();
+    let responses = serialized.as_array().unwrap();
+    assert_eq!(responses.len(), 3);
+    assert_eq!(responses[0]["id"], 1);
+    assert_eq!(responses[1]["error"]["code"], -32601);
+    assert_eq!(responses[2]["error"]["code"], -32600);
+
+    let response = server
+        .handle_message(r#"[{"jsonrpc":"2.0","method":"notifications/initialized"}]"#)
+        .await;
+    assert!(response.is_none());
+
+    let response = server.handle_message("[]").await.unwrap();
+    assert_eq!(error_code(&response), -32600);
+
+    let response = server
+        .handle_message(r#"[{"jsonrpc":"2.0","id":1,"method":"initialize","params":{"protocolVersion":"2025-03-26","capabilities":{}}}]"#)
+        .await
+        .unwrap();
+    assert_eq!(serde_json::to_value(&response).unwrap()[0]["error"]["code"], -32600);
+}
+
+#[tokio::test]
+async fn test_batches_are_rejected_from_2025_06_18() {
+    let server = McpServer::new();
+    server
+        .handle_message(r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{"protocolVersion":"2025-06-18","capabilities":{}}}"#)
+        .await
+        .unwrap();
+
+    let response = server
+        .handle_message(r#"[{"jsonrpc":"2.0","id":2,"method":"ping"}]"#)
+        .await
+        .unwrap();
+    let serialized = serde_json::to_value(&response).unwrap();
+    assert_eq!(serialized["id"], serde_json::Value::Null);
+    assert_eq!(serialized["error"]["code"], -32600);
+}
+
+#[test]
+fn test_structured_output_follows_protocol_version() {
+    let tool = get_my_notion_mcp::utils::create_get_latest_commit_tool();
+    let serialized = serde_json::to_value(&tool).unwrap();
+    assert!(serialized.get("inputSchema").is_some());
+    assert_eq!(serialized["outputSchema"]["required"], serde_json::json!(["sha", "message"]));
+    let serialized = serde_json::to_value(tool.for_protocol(ProtocolVersion::V2025_03_26)).unwrap();
+    assert!(serialized.get("outputSchema").is_none());
+
+    let commit: get_my_notion_mcp::github::GitHubCommit = serde_json::from_value(serde_json::json!({
+        "sha": "abc123",
+        "commit": { "message": "Add notes", "author": { "name": "Park", "date": "2025-01-01T00:00:00Z" } },
+        "html_url": "https://github.com/owner/repo/commit/abc123"
+    }))
+    .unwrap();
+    let result = get_my_notion_mcp::utils::create_structured_tool_result(
+        "Latest commit SHA: abc123".to_string(),
+        get_my_notion_mcp::utils::create_commit_output(&commit),
+    );
+
+    let serialized = serde_json::to_value(result.clone().for_protocol(ProtocolVersion::V2025_06_18)).unwrap();
+    assert_eq!(serialized["structuredContent"]["sha"], "abc123");
+    assert_eq!(serialized["structuredContent"]["author"], "Park");
+    assert_eq!(serialized["content"][0]["text"], "Latest commit SHA: abc123");
+
+    let serialized = serde_json::to_value(result.for_protocol(ProtocolVersion::V2024_11_05)).unwrap();
+    assert!(serialized.get("structuredContent").is_none());
+}
+
+#[tokio::test]
+async fn test_builder_server_serves_in_process() {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    let server = McpServer::builder()
+        .name("team-notes")
+        .version("1.2.3")
+        .instructions("Read team notes before answering.")
+        .tool(get_my_notion_mcp::utils::create_get_latest_commit_tool(), GetLatestCommitHandler::new())
+        .build();
+
+    let (mut client_writer, server_reader) = tokio::io::duplex(4096);
+    let (server_writer, client_reader) = tokio::io::duplex(4096);
+    let serving = tokio::spawn(server.serve(server_reader, server_writer));
+    let mut lines = BufReader::new(client_reader).lines();
+
+    client_writer
+        .write_all(b"{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"initialize\",\"params\":{\"protocolVersion\":\"2025-06-18\",\"capabilities\":{}}}\n")
+        .await
+        .unwrap();
+    let response: serde_json::Value = serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+    assert_eq!(response["result"]["serverInfo"]["name"], "team-notes");
+    assert_eq!(response["result"]["serverInfo"]["version"], "1.2.3");
+    assert_eq!(response["result"]["instructions"], "Read team notes before answering.");
+
+    client_writer
+        .write_all(b"{\"jsonrpc\":\"2.0\",\"id\":2,\"method\":\"tools/list\"}\n")
+        .await
+        .unwrap();
+    let response: serde_json::Value = serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+    assert_eq!(response["result"]["tools"][0]["name"], "get_latest_commit");
+
+    drop(client_writer);
+    serving.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn test_instructions_can_be_replaced_at_runtime() {
+    let server = McpServer::builder().instructions("Starting up.").build();
+    let initialize = r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{"protocolVersion":"2025-06-18","capabilities":{}}}"#;
+
+    let response = server.handle_message(initialize).await.unwrap();
+    assert_eq!(serde_json::to_value(&response).unwrap()["result"]["instructions"], "Starting up.");
+
+    server.handle().set_instructions("Top-level entries: notes/.");
+    let response = server.handle_message(initialize).await.unwrap();
+    assert_eq!(serde_json::to_value(&response).unwrap()["result"]["instructions"], "Top-level entries: notes/.");
+}
+
+#[tokio::test]
+async fn test_list_requests_are_paginated() {
+    let server = McpServer::builder()
+        .page_size(2)
+        .tool(get_my_notion_mcp::utils::create_list_files_tool(), ListFilesHandler::new())
+        .tool(get_my_notion_mcp::utils::create_get_file_content_tool(), GetFileContentHandler::new())
+        .tool(get_my_notion_mcp::utils::create_get_latest_commit_tool(), GetLatestCommitHandler::new())
+        .build();
+
+    let response = server
+        .handle_message(r#"{"jsonrpc":"2.0","id":1,"method":"tools/list"}"#)
+        .await
+        .unwrap();
+    let serialized = serde_json::to_value(&response).unwrap();
+    assert_eq!(serialized["result"]["tools"].as_array().unwrap().len(), 2);
+    let cursor = serialized["result"]["nextCursor"].as_str().unwrap().to_string();
+
+    let request = serde_json::json!({"jsonrpc": "2.0", "id": 2, "method": "tools/list", "params": {"cursor": cursor}});
+    let response = server.handle_message(&request.to_string()).await.unwrap();
+    let serialized = serde_json::to_value(&response).unwrap();
+    assert_eq!(serialized["result"]["tools"][0]["name"], "get_latest_commit");
+    assert!(serialized["result"].get("nextCursor").is_none());
+
+    let response = server
+        .handle_message(r#"{"jsonrpc":"2.0","id":3,"method":"resources/list","params":{"cursor":"not-a-cursor"}}"#)
+        .await
+        .unwrap();
+    assert_eq!(error_code(&response), -32602);
+}
+
+#[test]
+fn test_paginate_walks_every_item_once() {
+    let items: Vec<u32> = (0..5).collect();
+    let mut seen = Vec::new();
+    let mut cursor: Option<String> = None;
+    loop {
+        let (page, next_cursor) = get_my_notion_mcp::utils::paginate(items.clone(), cursor.as_deref(), 2).unwrap();
+        seen.extend(page);
+        match next_cursor {
+            Some(next_cursor) => cursor = Some(next_cursor),
+            None => break,
+        }
+    }
+    assert_eq!(seen, items);
+
+    let (page, next_cursor) = get_my_notion_mcp::utils::paginate(Vec::<u32>::new(), None, 2).unwrap();
+    assert!(page.is_empty() && next_cursor.is_none());
+}
+
+#[tokio::test]
+async fn test_set_level_is_acknowledged() {
+    let server = McpServer::new();
+
+    let response = server
+        .handle_message(r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{"protocolVersion":"2025-06-18","capabilities":{}}}"#)
+        .await
+        .unwrap();
+    assert_eq!(serde_json::to_value(&response).unwrap()["result"]["capabilities"]["logging"], serde_json::json!({}));
+
+    let response = server
+        .handle_message(r#"{"jsonrpc":"2.0","id":2,"method":"logging/setLevel","params":{"level":"warning"}}"#)
+        .await
+        .unwrap();
+    assert_eq!(serde_json::to_value(&response).unwrap()["result"], serde_json::json!({}));
+
+    let response = server
+        .handle_message(r#"{"jsonrpc":"2.0","id":3,"method":"logging/setLevel","params":{"level":"loud"}}"#)
+        .await
+        .unwrap();
+    assert_eq!(error_code(&response), -32602);
+}
+
+#[tokio::test]
+async fn test_initialize_carries_server_metadata() {
+    let server = McpServer::builder()
+        .instructions(get_my_notion_mcp::utils::create_server_instructions("owner", "notes", &[]))
+        .build();
+
+    let response = server
+        .handle_message(r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{"protocolVersion":"2025-06-18","capabilities":{}}}"#)
+        .await
+        .unwrap();
+    let result = &serde_json::to_value(&response).unwrap()["result"];
+    assert_eq!(result["serverInfo"]["version"], env!("CARGO_PKG_VERSION"));
+    assert_eq!(result["serverInfo"]["title"], "My Notion");
+    assert!(result["serverInfo"]["websiteUrl"].is_string());
+    let instructions = result["instructions"].as_str().unwrap();
+    assert!(instructions.contains("owner/notes"));
+    assert!(instructions.find("list_files").unwrap() < instructions.find("get_file_content").unwrap());
+
+    let response = server
+        .handle_message(r#"{"jsonrpc":"2.0","id":2,"method":"initialize","params":{"protocolVersion":"2025-03-26","capabilities":{}}}"#)
+        .await
+        .unwrap();
+    let result = &serde_json::to_value(&response).unwrap()["result"];
+    assert!(result["serverInfo"].get("title").is_none());
+    assert!(result["serverInfo"].get("websiteUrl").is_none());
+}
+
+#[test]
+fn test_tool_annotations_follow_protocol_version() {
+    let tool = get_my_notion_mcp::utils::create_get_file_content_tool();
+
+    let serialized = serde_json::to_value(tool.for_protocol(ProtocolVersion::V2025_06_18)).unwrap();
+    assert_eq!(serialized["title"], "Get File Content");
+    assert_eq!(
+        serialized["annotations"],
+        serde_json::json!({ "readOnlyHint": true, "destructiveHint": false, "idempotentHint": true, "openWorldHint": true })
+    );
+
+    let serialized = serde_json::to_value(tool.for_protocol(ProtocolVersion::V2025_03_26)).unwrap();
+    assert!(serialized.get("title").is_none());
+    assert_eq!(serialized["annotations"]["readOnlyHint"], true);
+
+    let serialized = serde_json::to_value(tool.for_protocol(ProtocolVersion::V2024_11_05)).unwrap();
+    assert!(serialized.get("annotations").is_none());
+}
+
+// Asks the client's model to summarize a fixed note
+struct SamplingToolHandler;
+
+#[async_trait::async_trait]
+impl get_my_notion_mcp::server::ToolHandler for SamplingToolHandler {
+    async fn call(
+        &self,
+        _arguments: Option<HashMap<String, serde_json::Value>>,
+        context: &get_my_notion_mcp::server::ToolContext,
+    ) -> anyhow::Result<CallToolResult> {
+        let result = context
+            .create_message(get_my_notion_mcp::utils::create_summary_request("notes/day.md", "Shipped sampling."))
+            .await?;
+        let text = match result.content {
+            SamplingContent::Text { text } => text,
+            SamplingContent::Other => String::new(),
+        };
+        Ok(CallToolResult {
+            content: vec![ToolContent::Text { text }],
+            structured_content: None,
+            is_error: None,
+        })
+    }
+}
+
+#[tokio::test]
+async fn test_sampling_over_http_with_json_responses() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    drop(listener);
+
+    let server = McpServer::builder()
+        .tool(
+            Tool {
+                name: "sample".to_string(),
+                title: None,
+                description: "Summarizes a note with the client's model".to_string(),
+                input_schema: serde_json::json!({ "type": "object" }),
+                output_schema: None,
+                annotations: None,
+            },
+            SamplingToolHandler,
+        )
+        .build();
+    tokio::spawn(server.run_http(address));
+
+    let endpoint = format!("http://{}/mcp", address);
+    let client = reqwest::Client::new();
+    let mut response = None;
+    for _ in 0..50 {
+        let attempt = client
+            .post(&endpoint)
+            .header("accept", "application/json")
+            .body(r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{"protocolVersion":"2025-06-18","capabilities":{"sampling":{}}}}"#)
+            .send()
+            .await;
+        if let Ok(attempt) = attempt {
+            response = Some(attempt);
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    let session_id = response.unwrap().headers()["mcp-session-id"].to_str().unwrap().to_string();
+
+    let mut stream = client
+        .get(&endpoint)
+        .header("accept", "text/event-stream")
+        .header("mcp-session-id", &session_id)
+        .send()
+        .await
+        .unwrap();
+
+    let call = tokio::spawn(
+        client
+            .post(&endpoint)
+            .header("accept", "application/json")
+            .header("mcp-session-id", &session_id)
+            .body(r#"{"jsonrpc":"2.0","id":2,"method":"tools/call","params":{"name":"sample"}}"#)
+            .send(),
+    );
+
+    // The sampling request must reach the GET stream while the call is still open
+    let mut events = String::new();
+    let request: serde_json::Value = loop {
+        let chunk = tokio::time::timeout(std::time::Duration::from_secs(5), stream.chunk())
+            .await
+            .expect("sampling request was not forwarded")
+            .unwrap()
+            .unwrap();
+        events.push_str(&String::from_utf8_lossy(&chunk));
+        if let Some(data) = events.lines().find_map(|line| line.strip_prefix("data: ")) {
+            break serde_json::from_str(data).unwrap();
+        }
+    };
+    assert_eq!(request["method"], "sampling/createMessage");
+
+    let answer = serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": request["id"],
+        "result": { "role": "assistant", "content": { "type": "text", "text": "A short day." }, "model": "test-model" }
+    });
+    let accepted = client
+        .post(&endpoint)
+        .header("mcp-session-id", &session_id)
+        .body(answer.to_string())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(accepted.status(), reqwest::StatusCode::ACCEPTED);
+
+    let response: serde_json::Value = tokio::time::timeout(std::time::Duration::from_secs(5), call)
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(response["id"], 2);
+    assert_eq!(response["result"]["content"][0]["text"], "A short day.");
+}